@@ -53,6 +53,7 @@ pub struct CardDavClient {
     client: Client,
     username: String,
     app_password: String,
+    base_url: String,
 }
 
 impl CardDavClient {
@@ -61,13 +62,42 @@ impl CardDavClient {
             client: Client::new(),
             username,
             app_password,
+            base_url: CARDDAV_BASE.to_string(),
         }
     }
 
+    /// Use another CardDAV server; None keeps Fastmail. A URL containing `{username}`
+    /// is taken as the full address book home, otherwise Fastmail's layout is assumed.
+    pub fn with_base_url(mut self, url: Option<String>) -> Self {
+        if let Some(url) = url {
+            self.base_url = url.trim_end_matches('/').to_string();
+        }
+        self
+    }
+
+    fn home_url(&self) -> String {
+        if self.base_url.contains("{username}") {
+            format!("{}/", self.base_url.replace("{username}", &self.username))
+        } else {
+            format!("{}/dav/addressbooks/user/{}/", self.base_url, self.username)
+        }
+    }
+
+    /// Address book hrefs from PROPFIND are paths on the server's origin
+    fn href_url(&self, href: &str) -> String {
+        if href.starts_with("http://") || href.starts_with("https://") {
+            return href.to_string();
+        }
+        let origin = reqwest::Url::parse(&self.base_url)
+            .map(|u| u.origin().ascii_serialization())
+            .unwrap_or_else(|_| self.base_url.clone());
+        format!("{}{}", origin, href)
+    }
+
     /// Discover address books for the user
     #[instrument(skip(self))]
     pub async fn list_addressbooks(&self) -> Result<Vec<AddressBook>> {
-        let url = format!("{}/dav/addressbooks/user/{}/", CARDDAV_BASE, self.username);
+        let url = self.home_url();
 
         let body = r#"<?xml version="1.0" encoding="utf-8"?>
 <d:propfind xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
@@ -133,7 +163,7 @@ impl CardDavClient {
     /// List all contacts in an address book
     #[instrument(skip(self))]
     pub async fn list_contacts(&self, addressbook_href: &str) -> Result<Vec<Contact>> {
-        let url = format!("{}{}", CARDDAV_BASE, addressbook_href);
+        let url = self.href_url(addressbook_href);
 
         let body = r#"<?xml version="1.0" encoding="utf-8"?>
 <card:addressbook-query xmlns:d="DAV:" xmlns:card="urn:ietf:params:xml:ns:carddav">
@@ -187,7 +217,7 @@ impl CardDavClient {
         }
 
         // Sort by name
-        contacts.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        contacts.sort_by_key(|c| c.name.to_lowercase());
 
         Ok(contacts)
     }
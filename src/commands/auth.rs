@@ -1,16 +1,58 @@
-use crate::config::Config;
+use super::check_session;
+use crate::config::{Config, run_secret_command};
 use crate::jmap::JmapClient;
 use crate::models::Output;
 
-pub async fn auth(token: &str) -> anyhow::Result<()> {
-    let mut client = JmapClient::new(token.to_string());
-    let session = client.authenticate().await?;
+/// Verify and store a token. With `is_command`, `token` is a credential helper
+/// command that prints the token; only the command is saved. With `discover`, the
+/// server is found from an email address or domain and its session URL saved too.
+pub async fn auth(
+    mut config: Config,
+    token: &str,
+    is_command: bool,
+    discover: Option<&str>,
+) -> anyhow::Result<()> {
+    let secret = if is_command {
+        run_secret_command(token)?
+    } else {
+        token.to_string()
+    };
+    let session_url = match discover {
+        Some(address) => Some(JmapClient::discover_session_url(address).await?),
+        None => config.get_session_url(),
+    };
+    let mut client = JmapClient::new(secret)
+        .with_session_url(session_url.clone())
+        .with_account(config.get_account())
+        .with_retries(config.get_max_retries());
+    client.authenticate().await?;
 
-    let mut config = Config::load()?;
-    config.set_token(token.to_string());
+    let report = check_session(&client)?;
+    if !report.has_mail() {
+        anyhow::bail!(
+            "Token has no mail access; not saving it. Check its scopes in Fastmail settings."
+        );
+    }
+    for warning in report.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+    let username = client.session()?.username.clone();
+
+    if is_command {
+        config.set_token_command(token.to_string());
+    } else {
+        config.set_token(token.to_string());
+    }
+    if let (Some(_), Some(url)) = (discover, session_url) {
+        config.set_session_url(url);
+    }
     config.save()?;
 
-    Output::<()>::success_msg(format!("Authenticated as {}", session.username)).print();
+    let message = match config.profile() {
+        Some(profile) => format!("Authenticated as {} (profile {})", username, profile),
+        None => format!("Authenticated as {}", username),
+    };
+    Output::<()>::success_msg(message).print();
 
     Ok(())
 }
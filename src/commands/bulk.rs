@@ -0,0 +1,47 @@
+use crate::jmap::JmapClient;
+use crate::models::{BulkResult, Output};
+use std::io::Read;
+
+/// Emails selected on the command line for a bulk operation
+#[derive(Debug, Default)]
+pub struct EmailSelection {
+    /// Email IDs; "-" reads whitespace-separated IDs from stdin
+    pub ids: Vec<String>,
+    /// Query expression selecting emails (see `search --query`)
+    pub query: Option<String>,
+}
+
+impl EmailSelection {
+    /// Expand stdin and the query into a de-duplicated list of IDs
+    pub async fn resolve(&self, client: &JmapClient) -> anyhow::Result<Vec<String>> {
+        let mut ids: Vec<String> = Vec::new();
+        for id in &self.ids {
+            if id == "-" {
+                let mut buf = String::new();
+                std::io::stdin().read_to_string(&mut buf)?;
+                ids.extend(buf.split_whitespace().map(String::from));
+            } else {
+                ids.push(id.clone());
+            }
+        }
+        if let Some(ref query) = self.query {
+            ids.extend(client.query_email_ids(query).await?);
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        ids.retain(|id| seen.insert(id.clone()));
+        Ok(ids)
+    }
+}
+
+/// Print a bulk result, exiting non-zero if any email failed
+pub fn print_bulk(result: BulkResult, message: String) {
+    let ok = result.failed.is_empty();
+    let mut output = Output::success(result);
+    output.success = ok;
+    output.message = Some(message);
+    output.print();
+    if !ok {
+        std::process::exit(1);
+    }
+}
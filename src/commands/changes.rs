@@ -0,0 +1,114 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::jmap::JmapClient;
+use crate::models::{Changes, Output};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Last-seen state per data type for one account, in
+/// `<config dir>/state/<username>/<account id>.json`
+pub(super) struct StateStore {
+    path: PathBuf,
+    states: BTreeMap<String, String>,
+}
+
+impl StateStore {
+    pub(super) fn open(config: &Config, client: &JmapClient) -> Result<Self> {
+        let path = config
+            .state_dir()?
+            .join(file_name(&client.session()?.username))
+            .join(format!("{}.json", file_name(client.account_id()?)));
+        Self::load(path)
+    }
+
+    /// The store kept in `path`, empty if the file doesn't exist yet
+    pub(super) fn load(path: PathBuf) -> Result<Self> {
+        let states = match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, states })
+    }
+
+    pub(super) fn get(&self, data_type: &str) -> Option<String> {
+        self.states.get(data_type).cloned()
+    }
+
+    pub(super) fn save(&mut self, data_type: &str, state: &str) -> Result<()> {
+        self.states.insert(data_type.to_string(), state.to_string());
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&self.states)?)?;
+        Ok(())
+    }
+}
+
+/// Keep usernames and account IDs from escaping the state directory
+fn file_name(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "@.-_".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Print what changed since `since` (or the stored state) and remember the new state
+/// unless `save` is false. With no stored state, or `reset`, record the current state
+/// so the next run reports changes from now on.
+pub async fn changes(
+    config: &Config,
+    data_type: &str,
+    since: Option<&str>,
+    save: bool,
+    reset: bool,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let mut store = StateStore::open(config, &client)?;
+    let since = match since.map(String::from).or_else(|| store.get(data_type)) {
+        Some(since) if !reset => since,
+        _ => {
+            let state = client.current_state(data_type).await?;
+            store.save(data_type, &state)?;
+            let mut output = Output::success(Changes::none(state));
+            output.message = Some(if reset {
+                format!("Reset {} state to the current one", data_type)
+            } else {
+                format!(
+                    "No stored {} state; recorded the current one. Run again to see changes.",
+                    data_type
+                )
+            });
+            output.print();
+            return Ok(());
+        }
+    };
+
+    let changes = match client.changes(data_type, &since).await {
+        Ok(changes) => changes,
+        Err(Error::Jmap { error_type, .. }) if error_type == "cannotCalculateChanges" => {
+            anyhow::bail!(
+                "The server can no longer calculate {} changes since state {}; \
+                 run with --reset and re-sync",
+                data_type,
+                since
+            );
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    if save {
+        store.save(data_type, &changes.new_state)?;
+    }
+    Output::success(changes).print();
+
+    Ok(())
+}
@@ -3,12 +3,11 @@ use crate::config::Config;
 use crate::models::Output;
 
 /// List all contacts from all address books
-pub async fn list_contacts() -> anyhow::Result<()> {
-    let config = Config::load()?;
+pub async fn list_contacts(config: &Config) -> anyhow::Result<()> {
     let username = config.get_username()?;
     let app_password = config.get_app_password()?;
 
-    let client = CardDavClient::new(username, app_password);
+    let client = CardDavClient::new(username, app_password).with_base_url(config.get_carddav_url());
 
     let addressbooks = client.list_addressbooks().await?;
     eprintln!("Found {} address book(s)", addressbooks.len());
@@ -25,12 +24,11 @@ pub async fn list_contacts() -> anyhow::Result<()> {
 }
 
 /// Search contacts by name or email
-pub async fn search_contacts(query: &str) -> anyhow::Result<()> {
-    let config = Config::load()?;
+pub async fn search_contacts(config: &Config, query: &str) -> anyhow::Result<()> {
     let username = config.get_username()?;
     let app_password = config.get_app_password()?;
 
-    let client = CardDavClient::new(username, app_password);
+    let client = CardDavClient::new(username, app_password).with_base_url(config.get_carddav_url());
     let contacts = client.search_contacts(query).await?;
 
     Output::success(contacts).print();
@@ -0,0 +1,38 @@
+use super::{EmailSelection, print_bulk};
+use crate::config::Config;
+use crate::jmap::JmapClient;
+use serde_json::json;
+
+/// Move emails to Trash, or destroy them outright with `permanent`
+pub async fn delete(
+    config: &Config,
+    selection: &EmailSelection,
+    permanent: bool,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let ids = selection.resolve(&client).await?;
+    let result = if permanent {
+        client.destroy_emails(&ids).await?
+    } else {
+        let trash = client.find_mailbox("trash").await?;
+        client
+            .update_emails(&ids, json!({ "mailboxIds": { (trash.id): true } }))
+            .await?
+    };
+
+    let message = format!(
+        "{} {} of {} email(s)",
+        if permanent {
+            "Permanently deleted"
+        } else {
+            "Moved to Trash"
+        },
+        result.succeeded.len(),
+        ids.len()
+    );
+    print_bulk(result, message);
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use super::{AccountSummary, account_summaries};
+use crate::carddav::CardDavClient;
+use crate::config::Config;
+use crate::jmap::{CAPABILITIES, JmapClient, MASKED_EMAIL_CAPABILITY};
+use crate::models::{CoreCapabilities, Output};
+
+const MAIL_CAPABILITY: &str = "urn:ietf:params:jmap:mail";
+
+#[derive(serde::Serialize)]
+struct CapabilityCheck {
+    capability: &'static str,
+    granted: bool,
+}
+
+/// What the token can do, checked by both `auth` and `doctor`
+#[derive(serde::Serialize)]
+pub struct SessionReport {
+    username: String,
+    accounts: Vec<AccountSummary>,
+    capabilities: Vec<CapabilityCheck>,
+    /// Server limits from the core capability (maxObjectsInGet, maxCallsInRequest, ...)
+    limits: CoreCapabilities,
+    warnings: Vec<String>,
+    /// Missing optional extensions; not a problem, so they don't fail `doctor`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    notes: Vec<String>,
+}
+
+impl SessionReport {
+    /// Without mail access no command works, so the token isn't worth saving
+    pub fn has_mail(&self) -> bool {
+        self.capabilities
+            .iter()
+            .any(|c| c.capability == MAIL_CAPABILITY && c.granted)
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+}
+
+/// Compare the session against the capabilities the CLI relies on
+pub fn check_session(client: &JmapClient) -> anyhow::Result<SessionReport> {
+    let session = client.session()?;
+    let account_id = client.account_id()?;
+
+    let capabilities: Vec<CapabilityCheck> = CAPABILITIES
+        .iter()
+        .map(|&capability| CapabilityCheck {
+            capability,
+            granted: session.has_capability(capability),
+        })
+        .collect();
+
+    let mut warnings = Vec::new();
+    let mut notes = Vec::new();
+    for check in capabilities.iter().filter(|c| !c.granted) {
+        // Fastmail's own extension; other servers are healthy without it
+        if check.capability == MASKED_EMAIL_CAPABILITY {
+            notes.push("No masked email support - masked commands are unavailable".to_string());
+            continue;
+        }
+        warnings.push(match check.capability {
+            MAIL_CAPABILITY => "Token has no mail access - no email command will work".to_string(),
+            "urn:ietf:params:jmap:submission" => {
+                "Token can't send mail (read-only?) - send, reply, forward and draft send will fail"
+                    .to_string()
+            }
+            other => format!("Token lacks {}", other),
+        });
+    }
+    if let Some(account) = session.accounts.get(account_id).filter(|a| a.is_read_only) {
+        warnings.push(format!(
+            "Account {} is read-only - moving, flagging and deleting will fail",
+            account.name
+        ));
+    }
+
+    Ok(SessionReport {
+        username: session.username.clone(),
+        accounts: account_summaries(client)?,
+        capabilities,
+        limits: session.core_capabilities(),
+        warnings,
+        notes,
+    })
+}
+
+#[derive(serde::Serialize)]
+struct CardDavCheck {
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address_books: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Authenticate and report what the credentials allow, exiting non-zero on any problem
+pub async fn doctor(config: &Config) -> anyhow::Result<()> {
+    // Always check against a freshly fetched session
+    let mut client = JmapClient::from_config(config)?.with_session_cache(None);
+    client.authenticate().await?;
+
+    let report = check_session(&client)?;
+
+    let carddav = match (config.get_username(), config.get_app_password()) {
+        (Ok(username), Ok(app_password)) => {
+            match CardDavClient::new(username, app_password)
+                .with_base_url(config.get_carddav_url())
+                .list_addressbooks()
+                .await
+            {
+                Ok(books) => CardDavCheck {
+                    status: "ok",
+                    address_books: Some(books.len()),
+                    error: None,
+                },
+                Err(e) => CardDavCheck {
+                    status: "failed",
+                    address_books: None,
+                    error: Some(e.to_string()),
+                },
+            }
+        }
+        _ => CardDavCheck {
+            status: "not configured",
+            address_books: None,
+            error: None,
+        },
+    };
+
+    #[derive(serde::Serialize)]
+    struct DoctorResponse {
+        #[serde(flatten)]
+        session: SessionReport,
+        carddav: CardDavCheck,
+    }
+
+    let ok = report.warnings.is_empty() && carddav.status != "failed";
+    let mut output = Output::success(DoctorResponse {
+        session: report,
+        carddav,
+    });
+    output.success = ok;
+    output.print();
+    if !ok {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -5,16 +5,14 @@ use crate::util::{extract_text, infer_image_mime, is_image, parse_size, resize_i
 use std::path::Path;
 
 pub async fn download_attachment(
+    config: &Config,
     email_id: &str,
     output_dir: Option<&str>,
     format: Option<&str>,
     max_size: Option<&str>,
 ) -> anyhow::Result<()> {
     let max_bytes = max_size.and_then(parse_size);
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     let email = client.get_email(email_id).await?;
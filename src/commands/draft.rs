@@ -0,0 +1,207 @@
+use super::{ComposeInput, QueryOptions, parse_send_at};
+use crate::config::Config;
+use crate::error::Error;
+use crate::jmap::JmapClient;
+use crate::models::{Email, Output};
+use crate::util::parse_addresses;
+
+async fn get_draft(client: &JmapClient, email_id: &str) -> anyhow::Result<Email> {
+    let email = client.get_email(email_id).await?;
+    if !email.is_draft() {
+        return Err(Error::NotADraft(email_id.into()).into());
+    }
+    Ok(email)
+}
+
+#[derive(serde::Serialize)]
+struct DraftResponse {
+    email_id: String,
+}
+
+pub async fn draft_create(
+    config: &Config,
+    to: &str,
+    subject: &str,
+    body: &str,
+    cc: Option<&str>,
+    bcc: Option<&str>,
+    compose: &ComposeInput,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let to_addrs = parse_addresses(to);
+    let cc_addrs = cc.map(parse_addresses).unwrap_or_default();
+    let bcc_addrs = bcc.map(parse_addresses).unwrap_or_default();
+
+    let (body, options) = compose.resolve(&client, body).await?;
+
+    let email_id = client
+        .save_draft(
+            to_addrs, cc_addrs, bcc_addrs, subject, &body, &options, None,
+        )
+        .await?;
+
+    Output::success(DraftResponse { email_id }).print();
+    Ok(())
+}
+
+pub async fn draft_list(config: &Config, limit: u32) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let drafts = client.find_mailbox("drafts").await?;
+    let page = QueryOptions {
+        limit,
+        ..Default::default()
+    };
+    let emails: Vec<Email> = client
+        .list_emails(&drafts.id, &page)
+        .await?
+        .emails
+        .into_iter()
+        .filter(|e| e.is_draft())
+        .collect();
+
+    Output::success(emails).print();
+    Ok(())
+}
+
+pub async fn draft_show(config: &Config, email_id: &str) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let draft = get_draft(&client, email_id).await?;
+    Output::success(draft).print();
+    Ok(())
+}
+
+/// Replace a draft, keeping any field that isn't given
+#[allow(clippy::too_many_arguments)]
+pub async fn draft_edit(
+    config: &Config,
+    email_id: &str,
+    to: Option<&str>,
+    subject: Option<&str>,
+    body: Option<&str>,
+    cc: Option<&str>,
+    bcc: Option<&str>,
+    compose: &ComposeInput,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let draft = get_draft(&client, email_id).await?;
+
+    let to_addrs = to
+        .map(parse_addresses)
+        .unwrap_or_else(|| draft.to.clone().unwrap_or_default());
+    let cc_addrs = cc
+        .map(parse_addresses)
+        .unwrap_or_else(|| draft.cc.clone().unwrap_or_default());
+    let bcc_addrs = bcc
+        .map(parse_addresses)
+        .unwrap_or_else(|| draft.bcc.clone().unwrap_or_default());
+    let subject = subject.unwrap_or(draft.subject.as_deref().unwrap_or(""));
+
+    let new_body = compose.html.is_some() || compose.markdown || body.is_some();
+    let text = match body {
+        Some(body) => body,
+        // --html without --body regenerates the text part from the HTML
+        None if compose.html.is_some() => "",
+        None => draft.text_content().unwrap_or(""),
+    };
+
+    let (text, mut options) = compose.resolve(&client, text).await?;
+
+    if !new_body {
+        // htmlBody falls back to the text part, so only keep a real HTML part
+        let has_html = draft
+            .html_body
+            .as_ref()
+            .and_then(|parts| parts.first())
+            .and_then(|p| p.content_type.as_deref())
+            == Some("text/html");
+        if has_html {
+            options.html = draft.html_content().map(String::from);
+        }
+    }
+
+    let mut attachments: Vec<_> = draft.attachments.clone().unwrap_or_default();
+    attachments.append(&mut options.attachments);
+    options.attachments = attachments;
+
+    if options.from.is_none() {
+        options.from = draft
+            .from
+            .as_ref()
+            .and_then(|f| f.first())
+            .map(|a| a.email.clone());
+    }
+
+    let new_id = client
+        .save_draft(
+            to_addrs,
+            cc_addrs,
+            bcc_addrs,
+            subject,
+            &text,
+            &options,
+            Some(&draft),
+        )
+        .await?;
+
+    #[derive(serde::Serialize)]
+    struct EditResponse {
+        email_id: String,
+        replaced: String,
+    }
+
+    Output::success(EditResponse {
+        email_id: new_id,
+        replaced: email_id.to_string(),
+    })
+    .print();
+    Ok(())
+}
+
+pub async fn draft_send(
+    config: &Config,
+    email_id: &str,
+    send_at: Option<&str>,
+    delay: Option<&str>,
+) -> anyhow::Result<()> {
+    let send_at = parse_send_at(send_at, delay)?;
+
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let submission_id = client.send_draft(email_id, send_at).await?;
+
+    #[derive(serde::Serialize)]
+    struct SendResponse {
+        email_id: String,
+        submission_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        send_at: Option<String>,
+    }
+
+    Output::success(SendResponse {
+        email_id: email_id.to_string(),
+        submission_id,
+        send_at: send_at.map(|t| t.to_rfc3339()),
+    })
+    .print();
+    Ok(())
+}
+
+pub async fn draft_delete(config: &Config, email_id: &str) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    get_draft(&client, email_id).await?;
+    client.destroy_email(email_id).await?;
+
+    Output::<()>::success_msg(format!("Draft {} deleted", email_id)).print();
+    Ok(())
+}
@@ -1,19 +1,31 @@
+use super::ComposeInput;
 use crate::config::Config;
 use crate::jmap::JmapClient;
 use crate::models::Output;
 use crate::util::parse_addresses;
 
+/// How the original message is included when forwarding
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ForwardMode {
+    /// Quote the original body and re-attach its attachments
+    #[default]
+    Inline,
+    /// Attach the original message as message/rfc822
+    Attachment,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn forward(
+    config: &Config,
     email_id: &str,
     to: &str,
     body: &str,
     cc: Option<&str>,
     bcc: Option<&str>,
+    mode: ForwardMode,
+    compose: &ComposeInput,
 ) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     let original = client.get_email(email_id).await?;
@@ -22,19 +34,26 @@ pub async fn forward(
     let cc_addrs = cc.map(parse_addresses).unwrap_or_default();
     let bcc_addrs = bcc.map(parse_addresses).unwrap_or_default();
 
+    let (body, options) = compose.resolve(&client, body).await?;
+
     let new_email_id = client
-        .forward_email(&original, to_addrs, body, cc_addrs, bcc_addrs)
+        .forward_email(
+            &original, to_addrs, &body, cc_addrs, bcc_addrs, mode, &options,
+        )
         .await?;
 
     #[derive(serde::Serialize)]
     struct ForwardResponse {
         email_id: String,
         forwarded_from: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        send_at: Option<String>,
     }
 
     Output::success(ForwardResponse {
         email_id: new_email_id,
         forwarded_from: email_id.to_string(),
+        send_at: options.send_at.map(|t| t.to_rfc3339()),
     })
     .print();
 
@@ -0,0 +1,364 @@
+use super::SearchFilter;
+use super::changes::StateStore;
+use super::watch::{ChangeHandler, follow};
+use crate::config::{Config, HookConfig};
+use crate::error::{Error, Result};
+use crate::jmap::JmapClient;
+use crate::models::{Changes, Email, StateChange};
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing::debug;
+
+const DEFAULT_ATTEMPTS: u32 = 3;
+/// Wait before retrying a failed hook; doubles with each attempt
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Key in the per-account state file, kept apart from `changes email`
+const STATE_KEY: &str = "hooks";
+/// Newest `receivedAt` handled, to backfill from when the server can't list changes
+const RECEIVED_AT_KEY: &str = "hooks-received-at";
+
+struct Hook {
+    config: HookConfig,
+    /// Compiled Email/query filter
+    filter: Value,
+}
+
+struct HookRunner {
+    hooks: Vec<Hook>,
+    store: StateStore,
+    /// Emails a hook gave up on, one JSON object per line
+    failed_log: PathBuf,
+}
+
+impl ChangeHandler for HookRunner {
+    async fn state_changed(&mut self, _change: StateChange) -> Result<()> {
+        Ok(())
+    }
+
+    async fn emails_changed(&mut self, client: &JmapClient, changes: &Changes) -> Result<()> {
+        if !changes.created.is_empty() {
+            self.run_hooks(client, &changes.created).await?;
+        }
+        // Only now is the new mail handled, so a restart picks up from here
+        self.store.save(STATE_KEY, &changes.new_state)
+    }
+
+    /// Backfill by querying for mail received since the newest email already handled.
+    /// Without that time, the gap is recorded as a failure rather than skipped quietly.
+    async fn changes_lost(
+        &mut self,
+        client: &JmapClient,
+        since: &str,
+        new_state: &str,
+    ) -> Result<()> {
+        match self.backfill_since() {
+            Some(after) => {
+                eprintln!(
+                    "The server can no longer list changes since state {}; \
+                     running hooks for mail received after {}",
+                    since,
+                    after.to_rfc3339()
+                );
+                let ids = client.email_ids_since(&json!({}), after).await?;
+                self.run_hooks(client, &ids).await?;
+            }
+            None => {
+                let reason = format!(
+                    "the server can no longer list changes since state {} and there is \
+                     no receivedAt to backfill from; mail received in between was not checked",
+                    since
+                );
+                eprintln!(
+                    "Hooks skipped mail: {}; recorded in {}",
+                    reason,
+                    self.failed_log.display()
+                );
+                self.record_failure("*", "", &reason)?;
+            }
+        }
+        self.store.save(STATE_KEY, new_state)
+    }
+}
+
+impl HookRunner {
+    /// Where a backfill starts: just before the newest email handled so far
+    fn backfill_since(&self) -> Option<DateTime<Utc>> {
+        let at = self.store.get(RECEIVED_AT_KEY)?;
+        let at = DateTime::parse_from_rfc3339(&at).ok()?;
+        Some(at.with_timezone(&Utc) - chrono::Duration::seconds(1))
+    }
+
+    async fn run_hooks(&mut self, client: &JmapClient, created: &[String]) -> Result<()> {
+        // Ask the server which new emails each filter matches, looking only at mail
+        // received since the oldest of them
+        let summaries = client.get_email_summaries(created).await?;
+        let received: Vec<DateTime<Utc>> = summaries
+            .iter()
+            .filter_map(|e| e.received_at.as_deref())
+            .filter_map(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Utc))
+            .collect();
+        let (Some(&oldest), Some(&newest)) = (received.iter().min(), received.iter().max()) else {
+            return Ok(());
+        };
+        let since = oldest - chrono::Duration::seconds(1);
+
+        let mut emails: HashMap<String, Email> = HashMap::new();
+        for hook in &self.hooks {
+            let matches = client.email_ids_since(&hook.filter, since).await?;
+            for id in created.iter().filter(|id| matches.contains(id)) {
+                if !emails.contains_key(id) {
+                    match client.get_email(id).await {
+                        Ok(email) => emails.insert(id.clone(), email),
+                        // Gone again before we got to it
+                        Err(Error::EmailNotFound(_)) => continue,
+                        Err(e) => return Err(e),
+                    };
+                }
+                self.run_hook(hook, &emails[id]).await?;
+            }
+        }
+
+        let handled = self
+            .backfill_since()
+            .map(|t| t + chrono::Duration::seconds(1));
+        if handled.is_none_or(|t| newest > t) {
+            self.store.save(RECEIVED_AT_KEY, &newest.to_rfc3339())?;
+        }
+        Ok(())
+    }
+
+    /// Run a hook for one email, retrying with backoff. An email the hook keeps failing
+    /// on is written to the failure log; only failing to write that log is an error.
+    async fn run_hook(&self, hook: &Hook, email: &Email) -> Result<()> {
+        let name = &hook.config.name;
+        let input = serde_json::to_vec(email)?;
+        let attempts = hook.config.attempts.unwrap_or(DEFAULT_ATTEMPTS).max(1);
+        let mut delay = RETRY_DELAY;
+
+        for attempt in 1..=attempts {
+            let reason = match exec(&hook.config, &email.id, &input).await {
+                Ok(()) => {
+                    eprintln!("Hook {} ran for {}", name, email.id);
+                    return Ok(());
+                }
+                Err(reason) => reason,
+            };
+            if attempt < attempts {
+                eprintln!(
+                    "Hook {} failed for {} (attempt {}/{}): {}; retrying in {}s",
+                    name,
+                    email.id,
+                    attempt,
+                    attempts,
+                    reason,
+                    delay.as_secs()
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            } else {
+                eprintln!(
+                    "Hook {} failed for {} after {} attempt(s): {}; recorded in {}",
+                    name,
+                    email.id,
+                    attempts,
+                    reason,
+                    self.failed_log.display()
+                );
+                self.record_failure(name, &email.id, &reason)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn record_failure(&self, hook: &str, email_id: &str, reason: &str) -> Result<()> {
+        if let Some(dir) = self.failed_log.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.failed_log)?;
+        let entry = json!({
+            "at": Utc::now().to_rfc3339(),
+            "hook": hook,
+            "email_id": email_id,
+            "error": reason,
+        });
+        writeln!(file, "{}", entry)?;
+        Ok(())
+    }
+}
+
+/// Run the hook command through the shell with the email JSON on stdin.
+/// Err holds why it failed, including its stderr.
+async fn exec(hook: &HookConfig, email_id: &str, input: &[u8]) -> std::result::Result<(), String> {
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = tokio::process::Command::new("cmd");
+        command.args(["/C", &hook.command]);
+        command
+    };
+    #[cfg(not(windows))]
+    let mut command = {
+        let mut command = tokio::process::Command::new("sh");
+        command.args(["-c", &hook.command]);
+        command
+    };
+
+    let mut child = command
+        .env("FASTMAIL_HOOK", &hook.name)
+        .env("FASTMAIL_EMAIL_ID", email_id)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to start `{}`: {}", hook.command, e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that doesn't read its input closes the pipe early; that's fine
+        match stdin.write_all(input).await {
+            Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => {
+                return Err(format!(
+                    "failed to write the email to `{}`: {}",
+                    hook.command, e
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    let output = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("failed to run `{}`: {}", hook.command, e))?;
+    debug!(hook = %hook.name, stdout = %String::from_utf8_lossy(&output.stdout), "Hook output");
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// The `search` filter equivalent of a hook's match fields
+fn search_filter(hook: &HookConfig) -> SearchFilter {
+    SearchFilter {
+        query: hook.query.clone(),
+        text: hook.text.clone(),
+        from: hook.from.clone(),
+        to: hook.to.clone(),
+        subject: hook.subject.clone(),
+        has_attachment: hook.has_attachment,
+        ..Default::default()
+    }
+}
+
+/// Run the configured hooks for new mail until interrupted. Mail that arrived while
+/// not running is handled on start, from the state stored by the last run.
+pub async fn hooks_run(config: &Config) -> anyhow::Result<()> {
+    if config.hooks.is_empty() {
+        anyhow::bail!("No hooks configured. Add [[hooks]] entries to the config file.");
+    }
+
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    // Compile every filter up front so a typo fails now, not on the first email
+    let mut hooks = Vec::new();
+    for hook in &config.hooks {
+        let mailbox_id = match hook.mailbox {
+            Some(ref name) => Some(client.find_mailbox(name).await?.id),
+            None => None,
+        };
+        let filter = client
+            .search_filter(&search_filter(hook), mailbox_id.as_deref())
+            .await
+            .map_err(|e| anyhow::anyhow!("Hook {}: {}", hook.name, e))?;
+        hooks.push(Hook {
+            config: hook.clone(),
+            filter,
+        });
+    }
+
+    let mut store = StateStore::open(config, &client)?;
+    let state = match store.get(STATE_KEY) {
+        Some(state) => state,
+        None => {
+            let state = client.current_state("Email").await?;
+            store.save(STATE_KEY, &state)?;
+            store.save(RECEIVED_AT_KEY, &Utc::now().to_rfc3339())?;
+            state
+        }
+    };
+
+    eprintln!("Running {} hook(s); waiting for new mail", hooks.len());
+    let mut runner = HookRunner {
+        hooks,
+        store,
+        failed_log: config.state_dir()?.join("hooks-failed.jsonl"),
+    };
+    follow(&client, Some(state), &mut runner).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runner(dir: &std::path::Path) -> HookRunner {
+        HookRunner {
+            hooks: Vec::new(),
+            store: StateStore::load(dir.join("state.json")).unwrap(),
+            failed_log: dir.join("hooks-failed.jsonl"),
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("fastmail-cli-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_changes_lost_without_backfill_time_is_recorded() {
+        let dir = temp_dir("hooks-lost");
+        let mut runner = runner(&dir);
+        let client = JmapClient::new("unused".into());
+
+        runner.changes_lost(&client, "s1", "s9").await.unwrap();
+
+        let log = std::fs::read_to_string(dir.join("hooks-failed.jsonl")).unwrap();
+        let entry: Value = serde_json::from_str(log.trim()).unwrap();
+        assert_eq!(entry["hook"], "*");
+        assert!(entry["error"].as_str().unwrap().contains("state s1"));
+        assert_eq!(runner.store.get(STATE_KEY).as_deref(), Some("s9"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_backfill_since_newest_handled() {
+        let dir = temp_dir("hooks-backfill");
+        let mut runner = runner(&dir);
+        assert!(runner.backfill_since().is_none());
+
+        runner
+            .store
+            .save(RECEIVED_AT_KEY, "2026-03-02T08:00:00+00:00")
+            .unwrap();
+        assert_eq!(
+            runner.backfill_since().unwrap().to_rfc3339(),
+            "2026-03-02T07:59:59+00:00"
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,29 +1,87 @@
+use super::QueryOptions;
+use super::search::print_all;
 use crate::config::Config;
 use crate::jmap::JmapClient;
-use crate::models::{Email, Mailbox, Output};
+use crate::models::{Email, Mailbox, MailboxNode, Output};
 
-pub async fn list_mailboxes() -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+pub async fn list_mailboxes(config: &Config, tree: bool) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     let mailboxes = client.list_mailboxes().await?;
-    Output::success(mailboxes).print();
+    if tree {
+        Output::success(MailboxNode::tree(&mailboxes)).print();
+    } else {
+        Output::success(mailboxes).print();
+    }
 
     Ok(())
 }
 
-pub async fn list_emails(mailbox: &str, limit: u32) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
+pub async fn list_identities(config: &Config) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let identities = client.list_identities().await?;
+    Output::success(identities).print();
+
+    Ok(())
+}
+
+/// An entry in `list accounts` and `doctor` output
+#[derive(serde::Serialize)]
+pub struct AccountSummary {
+    id: String,
+    name: String,
+    is_personal: bool,
+    is_read_only: bool,
+    primary: bool,
+    selected: bool,
+}
+
+/// Every account in the session, primary first
+pub fn account_summaries(client: &JmapClient) -> anyhow::Result<Vec<AccountSummary>> {
+    let session = client.session()?;
+    let primary = session.primary_account_id();
+    let selected = client.account_id()?;
+    let mut accounts: Vec<AccountSummary> = session
+        .accounts
+        .iter()
+        .map(|(id, account)| AccountSummary {
+            id: id.clone(),
+            name: account.name.clone(),
+            is_personal: account.is_personal,
+            is_read_only: account.is_read_only,
+            primary: Some(id.as_str()) == primary,
+            selected: id == selected,
+        })
+        .collect();
+    accounts.sort_by(|a, b| (!a.primary, &a.name).cmp(&(!b.primary, &b.name)));
+    Ok(accounts)
+}
 
-    let mut client = JmapClient::new(token.to_string());
+pub async fn list_accounts(config: &Config) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    Output::success(account_summaries(&client)?).print();
+
+    Ok(())
+}
+
+pub async fn list_emails(
+    config: &Config,
+    mailbox: &str,
+    page: &QueryOptions,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     let mailbox = client.find_mailbox(mailbox).await?;
-    let emails = client.list_emails(&mailbox.id, limit).await?;
+    if page.all {
+        return print_all(&client, &serde_json::json!({"inMailbox": mailbox.id}), page).await;
+    }
+    let result = client.list_emails(&mailbox.id, page).await?;
 
     #[derive(serde::Serialize)]
     struct EmailListResponse {
@@ -31,7 +89,12 @@ pub async fn list_emails(mailbox: &str, limit: u32) -> anyhow::Result<()> {
         emails: Vec<Email>,
     }
 
-    Output::success(EmailListResponse { mailbox, emails }).print();
+    Output::success(EmailListResponse {
+        mailbox,
+        emails: result.emails,
+    })
+    .paged(result.total, result.next_page_token)
+    .print();
 
     Ok(())
 }
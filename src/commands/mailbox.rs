@@ -0,0 +1,182 @@
+use super::print_bulk;
+use crate::config::Config;
+use crate::error::Error;
+use crate::jmap::JmapClient;
+use crate::models::Output;
+use serde_json::json;
+
+#[derive(serde::Serialize)]
+struct MailboxResponse {
+    id: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_id: Option<String>,
+}
+
+/// Create a mailbox; without `parent`, a path like `Clients/Acme` creates
+/// `Acme` inside the existing `Clients`
+pub async fn mailbox_create(
+    config: &Config,
+    name: &str,
+    parent: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let (parent, name) = match (parent, name.trim_matches('/').rsplit_once('/')) {
+        (None, Some((parent, leaf))) => (Some(parent), leaf),
+        _ => (parent, name),
+    };
+    let parent_id = match parent {
+        Some(parent) => Some(client.find_mailbox(parent).await?.id),
+        None => None,
+    };
+    let id = client.create_mailbox(name, parent_id.as_deref()).await?;
+
+    Output::success(MailboxResponse {
+        id,
+        name: name.to_string(),
+        parent_id,
+    })
+    .print();
+    Ok(())
+}
+
+pub async fn mailbox_rename(config: &Config, name: &str, new_name: &str) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let mailbox = client.find_mailbox(name).await?;
+    client
+        .update_mailbox(&mailbox.id, json!({ "name": new_name }))
+        .await?;
+
+    Output::success(MailboxResponse {
+        id: mailbox.id,
+        name: new_name.to_string(),
+        parent_id: mailbox.parent_id,
+    })
+    .print();
+    Ok(())
+}
+
+/// Move a mailbox under `parent`, or to the top level when `parent` is None
+pub async fn mailbox_move(config: &Config, name: &str, parent: Option<&str>) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let mailbox = client.find_mailbox(name).await?;
+    let parent_id = match parent {
+        Some(parent) => Some(client.find_mailbox(parent).await?.id),
+        None => None,
+    };
+    client
+        .update_mailbox(&mailbox.id, json!({ "parentId": parent_id }))
+        .await?;
+
+    Output::success(MailboxResponse {
+        id: mailbox.id,
+        name: mailbox.name,
+        parent_id,
+    })
+    .print();
+    Ok(())
+}
+
+pub async fn mailbox_subscribe(
+    config: &Config,
+    name: &str,
+    subscribed: bool,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let mailbox = client.find_mailbox(name).await?;
+    client
+        .update_mailbox(&mailbox.id, json!({ "isSubscribed": subscribed }))
+        .await?;
+
+    let action = if subscribed {
+        "Subscribed to"
+    } else {
+        "Unsubscribed from"
+    };
+    Output::<()>::success_msg(format!("{} {}", action, mailbox.name)).print();
+    Ok(())
+}
+
+/// Give a mailbox a role such as `archive`, or clear it with None
+pub async fn mailbox_set_role(
+    config: &Config,
+    name: &str,
+    role: Option<&str>,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let mailbox = client.find_mailbox(name).await?;
+    client
+        .update_mailbox(&mailbox.id, json!({ "role": role }))
+        .await?;
+
+    let message = match role {
+        Some(role) => format!("{} is now the {} mailbox", mailbox.name, role),
+        None => format!("Cleared the role of {}", mailbox.name),
+    };
+    Output::<()>::success_msg(message).print();
+    Ok(())
+}
+
+/// Delete a mailbox; `remove_emails` also deletes emails that are only in it
+pub async fn mailbox_delete(
+    config: &Config,
+    name: &str,
+    remove_emails: bool,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let mailbox = client.find_mailbox(name).await?;
+    match client.destroy_mailbox(&mailbox.id, remove_emails).await {
+        Err(Error::Jmap { error_type, .. }) if error_type == "mailboxHasEmail" => {
+            anyhow::bail!(
+                "Mailbox {} still has {} email(s); use --remove-emails to delete them too",
+                mailbox.name,
+                mailbox.total_emails
+            )
+        }
+        Err(Error::Jmap { error_type, .. }) if error_type == "mailboxHasChild" => {
+            anyhow::bail!(
+                "Mailbox {} has child mailboxes; move or delete them first",
+                mailbox.name
+            )
+        }
+        result => result?,
+    }
+
+    Output::<()>::success_msg(format!("Mailbox {} deleted", mailbox.name)).print();
+    Ok(())
+}
+
+/// Empty a mailbox: destroy the emails only in it, and take emails that are also in
+/// other mailboxes out of this one so they survive there
+pub async fn empty_mailbox(config: &Config, name: &str) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let mailbox = client.find_mailbox(name).await?;
+    let plan = client.plan_empty_mailbox(&mailbox.id).await?;
+    let result = client.empty_mailbox(&mailbox.id, &plan).await?;
+
+    let message = format!(
+        "Emptied {}: deleted {} email(s), removed {} that are also in other mailboxes ({} of {} succeeded)",
+        mailbox.name,
+        plan.destroy.len(),
+        plan.remove.len(),
+        result.succeeded.len(),
+        plan.destroy.len() + plan.remove.len()
+    );
+    print_bulk(result, message);
+
+    Ok(())
+}
@@ -2,11 +2,8 @@ use crate::config::Config;
 use crate::jmap::JmapClient;
 use crate::models::Output;
 
-pub async fn list_masked_emails() -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+pub async fn list_masked_emails(config: &Config) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     let masked_emails = client.list_masked_emails().await?;
@@ -16,14 +13,12 @@ pub async fn list_masked_emails() -> anyhow::Result<()> {
 }
 
 pub async fn create_masked_email(
+    config: &Config,
     for_domain: Option<&str>,
     description: Option<&str>,
     prefix: Option<&str>,
 ) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     let masked_email = client
@@ -34,11 +29,8 @@ pub async fn create_masked_email(
     Ok(())
 }
 
-pub async fn enable_masked_email(id: &str) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+pub async fn enable_masked_email(config: &Config, id: &str) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     client
@@ -49,11 +41,8 @@ pub async fn enable_masked_email(id: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn disable_masked_email(id: &str) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+pub async fn disable_masked_email(config: &Config, id: &str) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     client
@@ -64,11 +53,8 @@ pub async fn disable_masked_email(id: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn delete_masked_email(id: &str) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+pub async fn delete_masked_email(config: &Config, id: &str) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     client
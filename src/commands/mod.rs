@@ -1,29 +1,47 @@
 mod auth;
+mod bulk;
+mod changes;
 mod contacts;
+mod delete;
+mod doctor;
 mod download;
+mod draft;
 mod forward;
 mod get;
+mod hooks;
 mod list;
+mod mailbox;
 mod masked;
 mod r#move;
+mod outbox;
 mod read;
 mod reply;
 mod search;
 mod send;
 mod spam;
 mod thread;
+mod watch;
 
 pub use auth::*;
+pub use bulk::*;
+pub use changes::*;
 pub use contacts::*;
+pub use delete::*;
+pub use doctor::*;
 pub use download::*;
+pub use draft::*;
 pub use forward::*;
 pub use get::*;
+pub use hooks::*;
 pub use list::*;
+pub use mailbox::*;
 pub use masked::*;
 pub use r#move::*;
+pub use outbox::*;
 pub use read::*;
 pub use reply::*;
 pub use search::*;
 pub use send::*;
 pub use spam::*;
 pub use thread::*;
+pub use watch::*;
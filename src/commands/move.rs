@@ -1,18 +1,35 @@
+use super::{EmailSelection, print_bulk};
 use crate::config::Config;
 use crate::jmap::JmapClient;
-use crate::models::Output;
+use serde_json::json;
 
-pub async fn move_email(email_id: &str, mailbox: &str) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+pub async fn move_email(
+    config: &Config,
+    selection: &EmailSelection,
+    mailbox: &str,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     let mailbox = client.find_mailbox(mailbox).await?;
-    client.move_email(email_id, &mailbox.id).await?;
+    if !mailbox.may_add_items() {
+        anyhow::bail!(
+            "Mailbox {} is read-only; you can't move emails into it",
+            mailbox.name
+        );
+    }
+    let ids = selection.resolve(&client).await?;
+    let result = client
+        .update_emails(&ids, json!({ "mailboxIds": { (mailbox.id): true } }))
+        .await?;
 
-    Output::<()>::success_msg(format!("Moved email to {}", mailbox.name)).print();
+    let message = format!(
+        "Moved {} of {} email(s) to {}",
+        result.succeeded.len(),
+        ids.len(),
+        mailbox.name
+    );
+    print_bulk(result, message);
 
     Ok(())
 }
@@ -0,0 +1,50 @@
+use crate::config::Config;
+use crate::jmap::JmapClient;
+use crate::models::{EmailAddress, Output};
+
+pub async fn outbox_list(config: &Config) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let (submissions, emails) = client.list_pending_submissions().await?;
+
+    #[derive(serde::Serialize)]
+    struct OutboxEntry {
+        id: String,
+        email_id: String,
+        send_at: Option<String>,
+        subject: Option<String>,
+        to: Vec<EmailAddress>,
+    }
+
+    let entries: Vec<OutboxEntry> = submissions
+        .into_iter()
+        .map(|s| {
+            let email = emails.iter().find(|e| e.id == s.email_id);
+            OutboxEntry {
+                subject: email.and_then(|e| e.subject.clone()),
+                to: email.and_then(|e| e.to.clone()).unwrap_or_default(),
+                id: s.id,
+                email_id: s.email_id,
+                send_at: s.send_at,
+            }
+        })
+        .collect();
+
+    Output::success(entries).print();
+    Ok(())
+}
+
+pub async fn outbox_cancel(config: &Config, submission_id: &str) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    client.cancel_submission(submission_id).await?;
+
+    Output::<()>::success_msg(format!(
+        "Submission {} canceled; the email was moved back to Drafts",
+        submission_id
+    ))
+    .print();
+    Ok(())
+}
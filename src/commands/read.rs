@@ -1,27 +1,57 @@
+use super::{EmailSelection, print_bulk};
 use crate::config::Config;
 use crate::jmap::JmapClient;
-use crate::models::Output;
+use serde_json::json;
 
-pub async fn mark_read(email_id: &str, read: bool) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+pub async fn mark_read(
+    config: &Config,
+    selection: &EmailSelection,
+    read: bool,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
-    let email = client.get_email(email_id).await?;
+    let ids = selection.resolve(&client).await?;
+    // null removes the keyword in a JMAP patch
+    let seen = if read { json!(true) } else { json!(null) };
+    let result = client
+        .update_emails(&ids, json!({ "keywords/$seen": seen }))
+        .await?;
+
+    let status = if read { "read" } else { "unread" };
+    let message = format!(
+        "Marked {} of {} email(s) as {}",
+        result.succeeded.len(),
+        ids.len(),
+        status
+    );
+    print_bulk(result, message);
+
+    Ok(())
+}
 
-    let mut keywords = email.keywords.clone();
-    if read {
-        keywords.insert("$seen".to_string(), true);
-    } else {
-        keywords.remove("$seen");
-    }
+pub async fn flag(
+    config: &Config,
+    selection: &EmailSelection,
+    flagged: bool,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
 
-    client.set_keywords(email_id, keywords).await?;
+    let ids = selection.resolve(&client).await?;
+    let value = if flagged { json!(true) } else { json!(null) };
+    let result = client
+        .update_emails(&ids, json!({ "keywords/$flagged": value }))
+        .await?;
 
-    let status = if read { "read" } else { "unread" };
-    Output::<()>::success_msg(format!("Email marked as {}", status)).print();
+    let status = if flagged { "Flagged" } else { "Unflagged" };
+    let message = format!(
+        "{} {} of {} email(s)",
+        status,
+        result.succeeded.len(),
+        ids.len()
+    );
+    print_bulk(result, message);
 
     Ok(())
 }
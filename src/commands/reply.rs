@@ -1,19 +1,19 @@
+use super::ComposeInput;
 use crate::config::Config;
 use crate::jmap::JmapClient;
 use crate::models::Output;
 use crate::util::parse_addresses;
 
 pub async fn reply(
+    config: &Config,
     email_id: &str,
     body: &str,
     reply_all: bool,
     cc: Option<&str>,
     bcc: Option<&str>,
+    compose: &ComposeInput,
 ) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     let original = client.get_email(email_id).await?;
@@ -21,19 +21,24 @@ pub async fn reply(
     let cc_addrs = cc.map(parse_addresses).unwrap_or_default();
     let bcc_addrs = bcc.map(parse_addresses).unwrap_or_default();
 
+    let (body, options) = compose.resolve(&client, body).await?;
+
     let new_email_id = client
-        .reply_email(&original, body, reply_all, cc_addrs, bcc_addrs)
+        .reply_email(&original, &body, reply_all, cc_addrs, bcc_addrs, &options)
         .await?;
 
     #[derive(serde::Serialize)]
     struct ReplyResponse {
         email_id: String,
         in_reply_to: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        send_at: Option<String>,
     }
 
     Output::success(ReplyResponse {
         email_id: new_email_id,
         in_reply_to: email_id.to_string(),
+        send_at: options.send_at.map(|t| t.to_rfc3339()),
     })
     .print();
 
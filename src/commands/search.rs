@@ -1,10 +1,15 @@
 use crate::config::Config;
+use crate::error::Error;
 use crate::jmap::JmapClient;
-use crate::models::Output;
+use crate::models::{Output, print_json_line};
+use crate::util::parse_sort;
+use serde_json::Value;
 
 /// Search filter matching JMAP Email/query FilterCondition
 #[derive(Debug, Default)]
 pub struct SearchFilter {
+    /// Query expression, e.g. `from:alice OR from:bob -subject:newsletter`
+    pub query: Option<String>,
     pub text: Option<String>,
     pub from: Option<String>,
     pub to: Option<String>,
@@ -22,11 +27,55 @@ pub struct SearchFilter {
     pub flagged: bool,
 }
 
-pub async fn search(filter: SearchFilter, limit: u32) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
+/// Paging for Email/query results
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    /// Page size
+    pub limit: u32,
+    /// Zero-based index of the first result
+    pub offset: u32,
+    /// Continue after this token (from a previous `next_page_token`)
+    pub page_token: Option<String>,
+    /// Follow page tokens until every result is fetched, printing each email as a JSON
+    /// line as its page arrives
+    pub all: bool,
+    /// JMAP sort property (see `util::parse_sort`)
+    pub sort: &'static str,
+    pub ascending: bool,
+    /// Return only one email per thread
+    pub collapse_threads: bool,
+}
+
+/// Parse a --sort value like "size" or "from:asc" (clap value parser)
+pub fn parse_sort_arg(spec: &str) -> Result<(&'static str, bool), String> {
+    parse_sort(spec).ok_or_else(|| {
+        format!(
+            "invalid sort '{}': use receivedAt, sentAt, size, from, to or subject, optionally with :asc or :desc",
+            spec
+        )
+    })
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            limit: 50,
+            offset: 0,
+            page_token: None,
+            all: false,
+            sort: "receivedAt",
+            ascending: false,
+            collapse_threads: false,
+        }
+    }
+}
 
-    let mut client = JmapClient::new(token.to_string());
+pub async fn search(
+    config: &Config,
+    filter: SearchFilter,
+    page: &QueryOptions,
+) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     // Resolve mailbox name to ID if specified
@@ -36,10 +85,39 @@ pub async fn search(filter: SearchFilter, limit: u32) -> anyhow::Result<()> {
         None
     };
 
-    let emails = client
-        .search_emails_filtered(&filter, mailbox_id.as_deref(), limit)
+    if page.all {
+        let filter = client.search_filter(&filter, mailbox_id.as_deref()).await?;
+        return print_all(&client, &filter, page).await;
+    }
+
+    let result = client
+        .search_emails_filtered(&filter, mailbox_id.as_deref(), page)
         .await?;
-    Output::success(emails).print();
+    Output::success(result.emails)
+        .paged(result.total, result.next_page_token)
+        .print();
 
     Ok(())
 }
+
+/// Stream every result of `filter` as one JSON email summary per line (`--all`), so the
+/// first emails show up without waiting for the last page
+pub(super) async fn print_all(
+    client: &JmapClient,
+    filter: &Value,
+    page: &QueryOptions,
+) -> anyhow::Result<()> {
+    let result = client
+        .query_all(filter, page, |result| {
+            for email in &result.emails {
+                print_json_line(email)?;
+            }
+            Ok(())
+        })
+        .await;
+    match result {
+        // The reader went away (e.g. `| head`): stop quietly
+        Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
@@ -1,6 +1,111 @@
 use crate::config::Config;
 use crate::jmap::JmapClient;
-use crate::models::{EmailAddress, Output};
+use crate::models::{EmailAddress, EmailBodyPart, Identity, Output};
+use crate::util::{html_to_text, markdown_to_html, parse_datetime, parse_duration};
+use chrono::{DateTime, Utc};
+use std::io::Read;
+
+/// Extra options shared by send, reply and forward
+#[derive(Debug, Default)]
+pub struct ComposeOptions {
+    /// Uploaded attachments (see `JmapClient::upload_attachments`)
+    pub attachments: Vec<EmailBodyPart>,
+    /// HTML alternative to the plain-text body
+    pub html: Option<String>,
+    /// Identity ID or address to send from (default: first identity)
+    pub from: Option<String>,
+    /// Identity already resolved by the caller (e.g. shown in a preview), sent with as is
+    /// instead of resolving `from` again; for a wildcard identity it carries the address
+    pub identity: Option<Identity>,
+    /// Append the identity's signature
+    pub signature: bool,
+    /// Hold the message on the server until this time
+    pub send_at: Option<DateTime<Utc>>,
+}
+
+/// Composition flags as given on the command line
+#[derive(Debug, Default)]
+pub struct ComposeInput {
+    /// Local files to attach
+    pub attach: Vec<String>,
+    /// HTML body source: a file path, or "-" for stdin
+    pub html: Option<String>,
+    /// Treat the body as Markdown and render an HTML alternative
+    pub markdown: bool,
+    /// Identity ID or address to send from
+    pub from: Option<String>,
+    /// Append the identity's signature
+    pub signature: bool,
+    /// When to send: RFC 3339 or "YYYY-MM-DD HH:MM" local time
+    pub send_at: Option<String>,
+    /// Send after this delay, e.g. "10m" or "2h"
+    pub delay: Option<String>,
+}
+
+impl ComposeInput {
+    /// Upload attachments and render the body, returning the plain-text body and options
+    pub async fn resolve(
+        &self,
+        client: &JmapClient,
+        body: &str,
+    ) -> anyhow::Result<(String, ComposeOptions)> {
+        // Check the send time first, so a typo fails before any attachment is uploaded
+        let send_at = parse_send_at(self.send_at.as_deref(), self.delay.as_deref())?;
+
+        let (text, html) = if let Some(ref source) = self.html {
+            let html = if source == "-" {
+                let mut buf = String::new();
+                std::io::stdin().read_to_string(&mut buf)?;
+                buf
+            } else {
+                std::fs::read_to_string(source)?
+            };
+            let text = if body.is_empty() {
+                html_to_text(&html).await
+            } else {
+                body.to_string()
+            };
+            (text, Some(html))
+        } else if self.markdown {
+            (body.to_string(), Some(markdown_to_html(body)))
+        } else {
+            (body.to_string(), None)
+        };
+
+        let options = ComposeOptions {
+            attachments: client.upload_attachments(&self.attach).await?,
+            html,
+            from: self.from.clone(),
+            signature: self.signature,
+            send_at,
+            ..Default::default()
+        };
+
+        Ok((text, options))
+    }
+}
+
+/// Resolve --send-at/--delay into an absolute send time
+pub fn parse_send_at(
+    send_at: Option<&str>,
+    delay: Option<&str>,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    if let Some(s) = send_at {
+        let at = parse_datetime(s).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid --send-at: {} (use RFC 3339 or YYYY-MM-DD HH:MM)",
+                s
+            )
+        })?;
+        return Ok(Some(at));
+    }
+    if let Some(s) = delay {
+        let delay = parse_duration(s)
+            .ok_or_else(|| anyhow::anyhow!("Invalid --delay: {} (e.g. 90s, 10m, 2h, 1d)", s))?;
+        return Ok(Some(Utc::now() + delay));
+    }
+    Ok(None)
+}
 
 fn parse_addresses(input: &str) -> Vec<EmailAddress> {
     input
@@ -30,34 +135,44 @@ fn parse_addresses(input: &str) -> Vec<EmailAddress> {
         .collect()
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn send(
+    config: &Config,
     to: &str,
     subject: &str,
     body: &str,
     cc: Option<&str>,
     bcc: Option<&str>,
     reply_to: Option<&str>,
+    compose: &ComposeInput,
 ) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
     let to_addrs = parse_addresses(to);
     let cc_addrs = cc.map(parse_addresses).unwrap_or_default();
     let bcc_addrs = bcc.map(parse_addresses).unwrap_or_default();
 
+    let (body, options) = compose.resolve(&client, body).await?;
+
     let email_id = client
-        .send_email(to_addrs, cc_addrs, bcc_addrs, subject, body, reply_to)
+        .send_email(
+            to_addrs, cc_addrs, bcc_addrs, subject, &body, reply_to, &options,
+        )
         .await?;
 
     #[derive(serde::Serialize)]
     struct SendResponse {
         email_id: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        send_at: Option<String>,
     }
 
-    Output::success(SendResponse { email_id }).print();
+    Output::success(SendResponse {
+        email_id,
+        send_at: options.send_at.map(|t| t.to_rfc3339()),
+    })
+    .print();
 
     Ok(())
 }
@@ -1,17 +1,24 @@
+use super::{EmailSelection, print_bulk};
 use crate::config::Config;
 use crate::jmap::JmapClient;
-use crate::models::Output;
+use serde_json::json;
 
-pub async fn mark_spam(email_id: &str) -> anyhow::Result<()> {
-    let config = Config::load()?;
-    let token = config.get_token()?;
-
-    let mut client = JmapClient::new(token.to_string());
+pub async fn mark_spam(config: &Config, selection: &EmailSelection) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
     client.authenticate().await?;
 
-    client.mark_spam(email_id).await?;
+    let junk = client.find_mailbox("junk").await?;
+    let ids = selection.resolve(&client).await?;
+    let result = client
+        .update_emails(&ids, json!({ "mailboxIds": { (junk.id): true } }))
+        .await?;
 
-    Output::<()>::success_msg("Email marked as spam").print();
+    let message = format!(
+        "Marked {} of {} email(s) as spam",
+        result.succeeded.len(),
+        ids.len()
+    );
+    print_bulk(result, message);
 
     Ok(())
 }
@@ -0,0 +1,247 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::jmap::JmapClient;
+use crate::models::{Changes, Email, StateChange, print_json_line};
+use serde::Serialize;
+use std::time::Duration;
+
+/// How often the server should ping an idle stream
+const PING: Duration = Duration::from_secs(30);
+const MIN_RECONNECT: Duration = Duration::from_secs(1);
+const MAX_RECONNECT: Duration = Duration::from_secs(60);
+
+/// What to do with the push stream, for `follow`
+pub(super) trait ChangeHandler {
+    /// A state change for the selected account, when not following Email changes
+    async fn state_changed(&mut self, change: StateChange) -> Result<()>;
+
+    /// Emails created, updated or destroyed since the last Email state. The state only
+    /// advances once this returns Ok.
+    async fn emails_changed(&mut self, client: &JmapClient, changes: &Changes) -> Result<()>;
+
+    /// The server can no longer list Email changes since `since` (`cannotCalculateChanges`).
+    /// Following resumes from `new_state`; anything in between must be recovered here.
+    async fn changes_lost(
+        &mut self,
+        client: &JmapClient,
+        since: &str,
+        new_state: &str,
+    ) -> Result<()>;
+}
+
+/// Follow the push stream until interrupted, reconnecting with backoff. With `email_state`,
+/// Email changes since that state (including any missed while disconnected) go to
+/// `emails_changed`; otherwise every state change goes to `state_changed`.
+pub(super) async fn follow(
+    client: &JmapClient,
+    email_state: Option<String>,
+    handler: &mut impl ChangeHandler,
+) -> anyhow::Result<()> {
+    let mut follower = Follower {
+        client,
+        account_id: client.account_id()?.to_string(),
+        email_state,
+        connected: false,
+    };
+
+    let mut delay = MIN_RECONNECT;
+    loop {
+        let result = follower.run(handler).await;
+        if std::mem::take(&mut follower.connected) {
+            delay = MIN_RECONNECT;
+        }
+        match result {
+            Ok(()) => eprintln!("Push stream closed; reconnecting in {}s", delay.as_secs()),
+            // The reader went away: stop quietly
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+            Err(e) if is_transient(&e) => eprintln!(
+                "Push stream failed: {}; reconnecting in {}s",
+                e,
+                delay.as_secs()
+            ),
+            // A revoked token, missing push support or a 4xx won't fix itself
+            Err(e) => return Err(e.into()),
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT);
+    }
+}
+
+/// Network trouble, rate limits and server errors are worth reconnecting after
+fn is_transient(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Http(_) | Error::Io(_) | Error::Json(_) | Error::RateLimited | Error::Server(_)
+    )
+}
+
+struct Follower<'a> {
+    client: &'a JmapClient,
+    account_id: String,
+    /// Last Email state handled; None when passing raw state changes through
+    email_state: Option<String>,
+    /// Set once a connection opens, so the reconnect backoff can start over
+    connected: bool,
+}
+
+impl Follower<'_> {
+    /// Stream until the server closes the connection or it fails
+    async fn run(&mut self, handler: &mut impl ChangeHandler) -> Result<()> {
+        let mut stream = self.client.event_source(PING).await?;
+        self.connected = true;
+        // Catch up on anything missed while disconnected
+        if self.email_state.is_some() {
+            self.catch_up(handler).await?;
+        }
+
+        while let Some(change) = stream.next().await? {
+            let Some(states) = change.changed.get(&self.account_id) else {
+                continue;
+            };
+            match self.email_state {
+                Some(ref known) => {
+                    if states.get("Email").is_some_and(|s| s != known) {
+                        self.catch_up(handler).await?;
+                    }
+                }
+                None => {
+                    handler
+                        .state_changed(StateChange {
+                            type_name: change.type_name.clone(),
+                            changed: [(self.account_id.clone(), states.clone())].into(),
+                        })
+                        .await?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand over the Email changes since the last known state
+    async fn catch_up(&mut self, handler: &mut impl ChangeHandler) -> Result<()> {
+        let Some(since) = self.email_state.clone() else {
+            return Ok(());
+        };
+        let changes = match self.client.changes("Email", &since).await {
+            Ok(changes) => changes,
+            Err(Error::Jmap { error_type, .. }) if error_type == "cannotCalculateChanges" => {
+                // Take the new state first, so mail arriving during recovery isn't missed
+                let new_state = self.client.current_state("Email").await?;
+                handler
+                    .changes_lost(self.client, &since, &new_state)
+                    .await?;
+                self.email_state = Some(new_state);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+
+        if !changes.created.is_empty()
+            || !changes.updated.is_empty()
+            || !changes.destroyed.is_empty()
+        {
+            handler.emails_changed(self.client, &changes).await?;
+        }
+        self.email_state = Some(changes.new_state);
+        Ok(())
+    }
+}
+
+/// One line of `watch --expand` output
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+enum EmailEvent<'a> {
+    Created { email: &'a Email },
+    Updated { email: &'a Email },
+    Destroyed { id: &'a str },
+}
+
+/// Prints each change as a JSON line, for `watch`
+struct Printer {
+    mailbox_id: Option<String>,
+}
+
+impl ChangeHandler for Printer {
+    async fn state_changed(&mut self, change: StateChange) -> Result<()> {
+        emit(&change)
+    }
+
+    async fn changes_lost(&mut self, _client: &JmapClient, since: &str, _: &str) -> Result<()> {
+        eprintln!(
+            "Warning: the server can no longer list changes since state {}; \
+             changes up to now were skipped",
+            since
+        );
+        Ok(())
+    }
+
+    async fn emails_changed(&mut self, client: &JmapClient, changes: &Changes) -> Result<()> {
+        let ids: Vec<String> = changes
+            .created
+            .iter()
+            .chain(&changes.updated)
+            .cloned()
+            .collect();
+        let emails = client.get_email_summaries(&ids).await?;
+        for email in &emails {
+            let elsewhere = self
+                .mailbox_id
+                .as_ref()
+                .is_some_and(|id| !email.mailbox_ids.get(id).copied().unwrap_or(false));
+            if elsewhere {
+                continue;
+            }
+            emit(&if changes.created.contains(&email.id) {
+                EmailEvent::Created { email }
+            } else {
+                EmailEvent::Updated { email }
+            })?;
+        }
+        // A destroyed email's mailboxes are unknown, so they only show unfiltered
+        if self.mailbox_id.is_none() {
+            for id in &changes.destroyed {
+                emit(&EmailEvent::Destroyed { id })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write one JSON line and flush it straight to the consumer
+fn emit(value: &impl Serialize) -> Result<()> {
+    Ok(print_json_line(value)?)
+}
+
+/// Follow the push stream, printing one JSON line per state change, or with `expand`
+/// one line per created, updated or destroyed email. Reconnects until interrupted.
+pub async fn watch(config: &Config, expand: bool, mailbox: Option<&str>) -> anyhow::Result<()> {
+    let mut client = JmapClient::from_config(config)?;
+    client.authenticate().await?;
+
+    let mailbox_id = match mailbox {
+        Some(name) => Some(client.find_mailbox(name).await?.id),
+        None => None,
+    };
+    // Filtering by mailbox needs the emails themselves
+    let email_state = if expand || mailbox_id.is_some() {
+        Some(client.current_state("Email").await?)
+    } else {
+        None
+    };
+
+    follow(&client, email_state, &mut Printer { mailbox_id }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&Error::Server("503".into())));
+        assert!(is_transient(&Error::RateLimited));
+        assert!(!is_transient(&Error::Config("no eventSourceUrl".into())));
+        assert!(!is_transient(&Error::InvalidToken("revoked".into())));
+        assert!(!is_transient(&Error::MissingCapability("push".into())));
+    }
+}
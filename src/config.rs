@@ -1,19 +1,74 @@
 use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct Config {
     #[serde(default)]
     pub core: CoreConfig,
     #[serde(default)]
     pub contacts: ContactsConfig,
+    /// Named logins, e.g. `[profiles.work.core]`; the top-level sections are the default profile
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Commands `hooks run` starts for matching new mail (`[[hooks]]`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<HookConfig>,
+    /// Profile selected for this process, if any
+    #[serde(skip)]
+    profile: Option<String>,
+    /// Account chosen with the global `--account` flag
+    #[serde(skip)]
+    account_override: Option<String>,
+    /// Set by the global `--no-cache` flag
+    #[serde(skip)]
+    no_session_cache: bool,
+}
+
+/// A `[[hooks]]` entry: run `command` for each new email matching the filter fields,
+/// which work like the `search` options. No filter fields matches every new email.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HookConfig {
+    /// Name used in logs and the `FASTMAIL_HOOK` variable
+    pub name: String,
+    /// Shell command; the email is passed as JSON on stdin
+    pub command: String,
+    /// Query expression, as for `search --query`
+    pub query: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub subject: Option<String>,
+    pub text: Option<String>,
+    pub mailbox: Option<String>,
+    #[serde(default)]
+    pub has_attachment: bool,
+    /// Runs per email before giving up and recording the failure (default 3)
+    pub attempts: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Profile {
+    #[serde(default)]
+    pub core: CoreConfig,
+    #[serde(default)]
+    pub contacts: ContactsConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct CoreConfig {
     pub api_token: Option<String>,
+    /// Shell command that prints the API token, e.g. `pass show fastmail/token`
+    pub api_token_command: Option<String>,
+    /// Shared or delegated account to use instead of the primary one (name or ID)
+    pub account: Option<String>,
+    /// Retries for rate-limited or failed read requests (default 3, 0 disables)
+    pub max_retries: Option<u32>,
+    /// JMAP session URL for a non-Fastmail server (default Fastmail's)
+    pub session_url: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
@@ -21,6 +76,11 @@ pub struct ContactsConfig {
     pub username: Option<String>,
     /// App password for CardDAV - API tokens don't work for CardDAV
     pub app_password: Option<String>,
+    /// Shell command that prints the app password
+    pub app_password_command: Option<String>,
+    /// CardDAV server for a non-Fastmail server (default Fastmail's); may contain
+    /// `{username}` to give the full address book home URL
+    pub carddav_url: Option<String>,
 }
 
 impl Config {
@@ -39,15 +99,46 @@ impl Config {
 
     pub fn load() -> Result<Self> {
         let path = Self::config_path()?;
-        if !path.exists() {
-            return Ok(Self::default());
-        }
-        let content = fs::read_to_string(&path)?;
-        let config: Config = toml::from_str(&content)
-            .map_err(|e| Error::Config(format!("Failed to parse config: {}", e)))?;
+        let mut config = if path.exists() {
+            let content = fs::read_to_string(&path)?;
+            toml::from_str(&content)
+                .map_err(|e| Error::Config(format!("Failed to parse config: {}", e)))?
+        } else {
+            Self::default()
+        };
+        config.profile = std::env::var("FASTMAIL_PROFILE").ok();
         Ok(config)
     }
 
+    /// Use this profile instead of FASTMAIL_PROFILE (the `--profile` flag)
+    pub fn set_profile_override(&mut self, profile: String) {
+        self.profile = Some(profile);
+    }
+
+    /// The selected profile: `--profile`, then FASTMAIL_PROFILE. None is the default profile.
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    fn selected(&self) -> Result<(&CoreConfig, &ContactsConfig)> {
+        match self.profile {
+            Some(ref name) => self
+                .profiles
+                .get(name)
+                .map(|p| (&p.core, &p.contacts))
+                .ok_or_else(|| Error::Config(format!("Profile '{}' not found in config", name))),
+            None => Ok((&self.core, &self.contacts)),
+        }
+    }
+
+    /// Config file section holding the selected profile's `section`, for messages
+    fn section_name(&self, section: &str) -> String {
+        match self.profile {
+            Some(ref name) => format!("profiles.{}.{}", name, section),
+            None => section.to_string(),
+        }
+    }
+
     pub fn save(&self) -> Result<()> {
         let dir = Self::config_dir()?;
         fs::create_dir_all(&dir)?;
@@ -72,12 +163,88 @@ impl Config {
         Ok(())
     }
 
-    /// Get the API token, preferring FASTMAIL_API_TOKEN env var over config file
+    /// Get the API token, preferring FASTMAIL_API_TOKEN env var, then `api_token_command`,
+    /// then the token stored in the config file
     pub fn get_token(&self) -> Result<String> {
         if let Ok(token) = std::env::var("FASTMAIL_API_TOKEN") {
             return Ok(token);
         }
-        self.core.api_token.clone().ok_or(Error::NotAuthenticated)
+        let core = self.selected()?.0;
+        if let Some(ref command) = core.api_token_command {
+            return run_secret_command(command);
+        }
+        core.api_token.clone().ok_or(Error::NotAuthenticated)
+    }
+
+    /// Use this account instead of FASTMAIL_ACCOUNT and the config file (the `--account` flag)
+    pub fn set_account_override(&mut self, account: String) {
+        self.account_override = Some(account);
+    }
+
+    /// Get the account to act on: `--account`, then FASTMAIL_ACCOUNT, then the profile's config.
+    /// None means the primary account.
+    pub fn get_account(&self) -> Option<String> {
+        if let Some(ref account) = self.account_override {
+            return Some(account.clone());
+        }
+        if let Ok(account) = std::env::var("FASTMAIL_ACCOUNT") {
+            return Some(account);
+        }
+        self.selected()
+            .ok()
+            .and_then(|(core, _)| core.account.clone())
+    }
+
+    /// Always fetch the JMAP session (the `--no-cache` flag)
+    pub fn disable_session_cache(&mut self) {
+        self.no_session_cache = true;
+    }
+
+    /// Directory for cached JMAP sessions, or None when caching is off
+    pub fn session_cache_dir(&self) -> Option<PathBuf> {
+        if self.no_session_cache {
+            return None;
+        }
+        Self::config_dir().ok().map(|dir| dir.join("sessions"))
+    }
+
+    /// Directory for the last-seen JMAP state strings used by `changes`
+    pub fn state_dir(&self) -> Result<PathBuf> {
+        Ok(Self::config_dir()?.join("state"))
+    }
+
+    /// Get the retry count, preferring FASTMAIL_MAX_RETRIES env var over config file
+    pub fn get_max_retries(&self) -> u32 {
+        std::env::var("FASTMAIL_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .or_else(|| self.selected().ok().and_then(|(core, _)| core.max_retries))
+            .unwrap_or(DEFAULT_MAX_RETRIES)
+    }
+
+    /// Get the JMAP session URL, preferring FASTMAIL_SESSION_URL env var over config file.
+    /// None means Fastmail.
+    pub fn get_session_url(&self) -> Option<String> {
+        std::env::var("FASTMAIL_SESSION_URL").ok().or_else(|| {
+            self.selected()
+                .ok()
+                .and_then(|(core, _)| core.session_url.clone())
+        })
+    }
+
+    /// Point the selected profile at another JMAP server
+    pub fn set_session_url(&mut self, url: String) {
+        self.core_mut().session_url = Some(url);
+    }
+
+    /// Get the CardDAV URL, preferring FASTMAIL_CARDDAV_URL env var over config file.
+    /// None means Fastmail.
+    pub fn get_carddav_url(&self) -> Option<String> {
+        std::env::var("FASTMAIL_CARDDAV_URL").ok().or_else(|| {
+            self.selected()
+                .ok()
+                .and_then(|(_, contacts)| contacts.carddav_url.clone())
+        })
     }
 
     /// Get the username (email), preferring FASTMAIL_USERNAME env var over config file
@@ -85,26 +252,81 @@ impl Config {
         if let Ok(username) = std::env::var("FASTMAIL_USERNAME") {
             return Ok(username);
         }
-        self.contacts
-            .username
-            .clone()
-            .ok_or_else(|| Error::Config("Username not set in [contacts] config.".into()))
+        self.selected()?.1.username.clone().ok_or_else(|| {
+            Error::Config(format!(
+                "Username not set in [{}] config.",
+                self.section_name("contacts")
+            ))
+        })
     }
 
+    /// Store the token in the selected profile, creating it if needed
     pub fn set_token(&mut self, token: String) {
-        self.core.api_token = Some(token);
+        let core = self.core_mut();
+        core.api_token = Some(token);
+        core.api_token_command = None;
+    }
+
+    /// Fetch the token from a command from now on, removing any stored token
+    pub fn set_token_command(&mut self, command: String) {
+        let core = self.core_mut();
+        core.api_token_command = Some(command);
+        core.api_token = None;
     }
 
-    /// Get the app password for CardDAV, preferring FASTMAIL_APP_PASSWORD env var
+    fn core_mut(&mut self) -> &mut CoreConfig {
+        match self.profile {
+            Some(ref name) => &mut self.profiles.entry(name.clone()).or_default().core,
+            None => &mut self.core,
+        }
+    }
+
+    /// Get the app password for CardDAV, preferring FASTMAIL_APP_PASSWORD env var, then
+    /// `app_password_command`
     pub fn get_app_password(&self) -> Result<String> {
         if let Ok(password) = std::env::var("FASTMAIL_APP_PASSWORD") {
             return Ok(password);
         }
-        self.contacts
-            .app_password
-            .clone()
-            .ok_or_else(|| Error::Config("App password not set in [contacts] config.".into()))
+        let contacts = self.selected()?.1;
+        if let Some(ref command) = contacts.app_password_command {
+            return run_secret_command(command);
+        }
+        contacts.app_password.clone().ok_or_else(|| {
+            Error::Config(format!(
+                "App password not set in [{}] config.",
+                self.section_name("contacts")
+            ))
+        })
+    }
+}
+
+/// Run a credential helper through the shell and return its trimmed stdout
+pub fn run_secret_command(command: &str) -> Result<String> {
+    #[cfg(windows)]
+    let output = std::process::Command::new("cmd")
+        .args(["/C", command])
+        .output();
+    #[cfg(not(windows))]
+    let output = std::process::Command::new("sh")
+        .args(["-c", command])
+        .output();
+
+    let output =
+        output.map_err(|e| Error::Config(format!("Failed to run `{}`: {}", command, e)))?;
+    if !output.status.success() {
+        return Err(Error::Config(format!(
+            "`{}` failed ({}): {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let secret = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if secret.is_empty() {
+        return Err(Error::Config(format!("`{}` printed nothing", command)));
     }
+    Ok(secret)
 }
 
 #[cfg(test)]
@@ -129,6 +351,7 @@ mod tests {
         let config = Config {
             core: CoreConfig {
                 api_token: Some("test-token".to_string()),
+                ..Default::default()
             },
             ..Default::default()
         };
@@ -147,6 +370,7 @@ mod tests {
         let config = Config {
             core: CoreConfig {
                 api_token: Some("test-token".to_string()),
+                ..Default::default()
             },
             ..Default::default()
         };
@@ -154,4 +378,189 @@ mod tests {
         let deserialized: Config = toml::from_str(&toml_str).unwrap();
         assert_eq!(deserialized.core.api_token, Some("test-token".to_string()));
     }
+
+    #[test]
+    fn test_config_account_from_file() {
+        let config: Config = toml::from_str(
+            r#"
+            [core]
+            api_token = "t"
+            account = "support@example.com"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.core.account.as_deref(), Some("support@example.com"));
+        assert!(Config::default().core.account.is_none());
+    }
+
+    #[test]
+    fn test_config_profiles() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [core]
+            api_token = "personal-token"
+
+            [profiles.work.core]
+            api_token = "work-token"
+            account = "support@example.com"
+
+            [profiles.work.contacts]
+            username = "me@work.example.com"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.get_token().unwrap(), "personal-token");
+        assert!(config.get_account().is_none());
+
+        config.profile = Some("work".to_string());
+        assert_eq!(config.get_token().unwrap(), "work-token");
+        assert_eq!(config.get_username().unwrap(), "me@work.example.com");
+        assert_eq!(config.get_account().as_deref(), Some("support@example.com"));
+        let err = config.get_app_password().unwrap_err().to_string();
+        assert!(err.contains("[profiles.work.contacts]"));
+
+        config.profile = Some("missing".to_string());
+        assert!(matches!(config.get_token(), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn test_config_set_token_profile() {
+        let mut config = Config {
+            profile: Some("work".to_string()),
+            ..Default::default()
+        };
+        config.set_token("work-token".to_string());
+        assert!(config.core.api_token.is_none());
+        assert_eq!(
+            config.profiles["work"].core.api_token.as_deref(),
+            Some("work-token")
+        );
+
+        let toml_str = toml::to_string(&config).unwrap();
+        assert!(toml_str.contains("[profiles.work.core]"));
+    }
+
+    #[test]
+    fn test_config_overrides() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [core]
+            account = "Shared"
+
+            [profiles.work.core]
+            account = "Team"
+            "#,
+        )
+        .unwrap();
+        assert!(config.session_cache_dir().is_some());
+
+        config.set_profile_override("work".to_string());
+        config.set_account_override("u1234".to_string());
+        config.disable_session_cache();
+        assert_eq!(config.profile(), Some("work"));
+        assert_eq!(config.get_account().as_deref(), Some("u1234"));
+        assert!(config.session_cache_dir().is_none());
+
+        // Overrides only live for this process
+        let toml_str = toml::to_string(&config).unwrap();
+        assert!(!toml_str.contains("u1234"));
+    }
+
+    #[test]
+    fn test_config_endpoints() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [profiles.ci.core]
+            session_url = "http://localhost:8080/.well-known/jmap"
+
+            [profiles.ci.contacts]
+            carddav_url = "http://localhost:8080/dav/card/{username}/"
+            "#,
+        )
+        .unwrap();
+        assert!(config.get_session_url().is_none());
+        assert!(config.get_carddav_url().is_none());
+
+        config.profile = Some("ci".to_string());
+        assert_eq!(
+            config.get_session_url().as_deref(),
+            Some("http://localhost:8080/.well-known/jmap")
+        );
+        assert_eq!(
+            config.get_carddav_url().as_deref(),
+            Some("http://localhost:8080/dav/card/{username}/")
+        );
+
+        config.profile = Some("other".to_string());
+        config.set_session_url("https://jmap.example.com/session".to_string());
+        assert_eq!(
+            config.profiles["other"].core.session_url.as_deref(),
+            Some("https://jmap.example.com/session")
+        );
+    }
+
+    #[test]
+    fn test_config_hooks() {
+        let config: Config = toml::from_str(
+            r#"
+            [core]
+            api_token = "t"
+
+            [profiles.work.core]
+            api_token = "w"
+
+            [[hooks]]
+            name = "invoices"
+            command = "~/bin/file-invoice"
+            from = "billing@vendor.example"
+            has_attachment = true
+
+            [[hooks]]
+            name = "all"
+            command = "notify-send 'New mail'"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.hooks.len(), 2);
+        assert_eq!(
+            config.hooks[0].from.as_deref(),
+            Some("billing@vendor.example")
+        );
+        assert!(config.hooks[0].has_attachment);
+        assert!(config.hooks[1].query.is_none());
+        assert!(config.hooks[1].attempts.is_none());
+
+        let toml_str = toml::to_string(&config).unwrap();
+        let reparsed: Config = toml::from_str(&toml_str).unwrap();
+        assert_eq!(reparsed.hooks.len(), 2);
+        assert!(
+            !toml::to_string(&Config::default())
+                .unwrap()
+                .contains("hooks")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_config_token_command() {
+        let mut config = Config::default();
+        config.set_token("plaintext".to_string());
+        config.set_token_command("printf 'from-helper\\n'".to_string());
+        assert!(config.core.api_token.is_none());
+        assert_eq!(config.get_token().unwrap(), "from-helper");
+
+        let toml_str = toml::to_string(&config).unwrap();
+        assert!(toml_str.contains("api_token_command = "));
+        assert!(!toml_str.contains("api_token = "));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_secret_command_errors() {
+        assert!(matches!(
+            run_secret_command("exit 3"),
+            Err(Error::Config(_))
+        ));
+        assert!(matches!(run_secret_command("true"), Err(Error::Config(_))));
+    }
 }
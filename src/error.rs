@@ -27,12 +27,36 @@ pub enum Error {
     #[error("Mailbox not found: {0}")]
     MailboxNotFound(String),
 
+    #[error("Mailbox name {0} is ambiguous; use a path: {candidates}", candidates = .1.join(", "))]
+    AmbiguousMailbox(String, Vec<String>),
+
     #[error("Email not found: {0}")]
     EmailNotFound(String),
 
+    #[error("Email {0} is not a draft")]
+    NotADraft(String),
+
     #[error("Identity not found for sending")]
     IdentityNotFound,
 
+    #[error("No identity matches {0}. Run `fastmail-cli list identities` to see them.")]
+    UnknownIdentity(String),
+
+    #[error("Identity {0} is a wildcard ({1}); give the address to send from instead of its ID")]
+    WildcardIdentity(String, String),
+
+    #[error("No account matches {0}. Run `fastmail-cli list accounts` to see them.")]
+    AccountNotFound(String),
+
+    #[error("The server doesn't support {0}. Run `fastmail-cli doctor` to see what it offers.")]
+    MissingCapability(String),
+
+    #[error("Failed to upload {name}: {reason}")]
+    Upload { name: String, reason: String },
+
+    #[error("Invalid search query: {0}")]
+    InvalidQuery(String),
+
     #[error("Config error: {0}")]
     Config(String),
 
@@ -0,0 +1,126 @@
+//! On-disk cache of the JMAP session, so each invocation can skip the session fetch.
+//!
+//! One file per server and token, named by a hash of the two (the token itself is
+//! never written). The cache is dropped when the server reports a new session state,
+//! rejects the token, or doesn't recognise a capability we declared.
+
+use crate::models::Session;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use tracing::debug;
+
+#[derive(Debug, Clone)]
+pub struct SessionCache {
+    path: PathBuf,
+}
+
+impl SessionCache {
+    pub fn new(dir: PathBuf, session_url: &str, token: &str) -> Self {
+        Self {
+            path: dir.join(format!("{}.json", key(session_url, token))),
+        }
+    }
+
+    pub fn load(&self) -> Option<Session> {
+        let content = fs::read_to_string(&self.path).ok()?;
+        match serde_json::from_str(&content) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                debug!(error = %e, "Ignoring unreadable session cache");
+                self.remove();
+                None
+            }
+        }
+    }
+
+    /// Best effort: a cache that can't be written just means fetching next time
+    pub fn store(&self, session: &Session) {
+        if let Err(e) = self.try_store(session) {
+            debug!(error = %e, path = %self.path.display(), "Failed to write session cache");
+        }
+    }
+
+    fn try_store(&self, session: &Session) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+            }
+        }
+
+        fs::write(&self.path, serde_json::to_vec(session)?)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&self.path, fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    pub fn remove(&self) {
+        if fs::remove_file(&self.path).is_ok() {
+            debug!(path = %self.path.display(), "Dropped session cache");
+        }
+    }
+}
+
+/// Hex SHA-256 of the session URL and token
+fn key(session_url: &str, token: &str) -> String {
+    Sha256::digest(format!("{}\n{}", session_url, token).as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(state: &str) -> Session {
+        serde_json::from_value(serde_json::json!({
+            "capabilities": {},
+            "accounts": {},
+            "primaryAccounts": {},
+            "username": "me@example.com",
+            "apiUrl": "https://api.example.com/jmap",
+            "downloadUrl": "https://api.example.com/download",
+            "uploadUrl": "https://api.example.com/upload",
+            "state": state
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_key_hides_token() {
+        let url = "https://api.fastmail.com/jmap/session";
+        let k = key(url, "fmu1-secret");
+        assert_eq!(k.len(), 64);
+        assert!(!k.contains("secret"));
+        assert_eq!(k, key(url, "fmu1-secret"));
+        assert_ne!(k, key(url, "fmu1-other"));
+        assert_ne!(k, key("http://localhost:8080/jmap/session", "fmu1-secret"));
+    }
+
+    #[test]
+    fn test_store_load_remove() {
+        let dir = std::env::temp_dir().join(format!("fastmail-cli-cache-{}", std::process::id()));
+        let url = "https://api.example.com/jmap/session";
+        let cache = SessionCache::new(dir.clone(), url, "token");
+        assert!(cache.load().is_none());
+
+        cache.store(&session("s1"));
+        assert_eq!(cache.load().unwrap().state.as_deref(), Some("s1"));
+        assert!(
+            SessionCache::new(dir.clone(), url, "other")
+                .load()
+                .is_none()
+        );
+
+        cache.remove();
+        assert!(cache.load().is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
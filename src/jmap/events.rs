@@ -0,0 +1,163 @@
+//! Push notifications over the JMAP EventSource endpoint (RFC 8620 section 7.3).
+//!
+//! The server sends `state` events carrying a `StateChange` whenever data changes,
+//! and `ping` events at the interval we ask for so a dead connection can be noticed.
+
+use crate::error::{Error, Result};
+use crate::models::StateChange;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tracing::debug;
+
+/// One server-sent event
+#[derive(Debug, PartialEq)]
+pub struct SseEvent {
+    /// `event:` field; "message" when absent
+    pub event: String,
+    pub data: String,
+}
+
+/// Incremental `text/event-stream` parser: feed it chunks as they arrive
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buf: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Consume a chunk and return every event it completes
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buf.extend_from_slice(chunk);
+        let mut events = Vec::new();
+
+        // Only complete lines are parsed, so a chunk split mid-character is safe
+        while let Some(end) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    events.push(SseEvent {
+                        event: self.event.take().unwrap_or_else(|| "message".into()),
+                        data: self.data.join("\n"),
+                    });
+                }
+                self.event = None;
+                self.data.clear();
+                continue;
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "event" => self.event = Some(value.to_string()),
+                "data" => self.data.push(value.to_string()),
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+/// An open EventSource connection, yielding `StateChange`s
+pub struct EventStream {
+    response: reqwest::Response,
+    parser: SseParser,
+    pending: VecDeque<SseEvent>,
+    /// Give up on the connection after this long without data or a ping
+    idle: Duration,
+}
+
+impl EventStream {
+    pub fn new(response: reqwest::Response, idle: Duration) -> Self {
+        Self {
+            response,
+            parser: SseParser::default(),
+            pending: VecDeque::new(),
+            idle,
+        }
+    }
+
+    /// The next state change, or None when the server closes the stream
+    pub async fn next(&mut self) -> Result<Option<StateChange>> {
+        loop {
+            while let Some(event) = self.pending.pop_front() {
+                match event.event.as_str() {
+                    "state" => return Ok(Some(serde_json::from_str(&event.data)?)),
+                    other => debug!(event = other, "Push event"),
+                }
+            }
+
+            let chunk = tokio::time::timeout(self.idle, self.response.chunk())
+                .await
+                .map_err(|_| Error::Server("Push stream went quiet (no ping)".into()))??;
+            match chunk {
+                Some(bytes) => self.pending.extend(self.parser.feed(&bytes)),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Fill in the session's `eventSourceUrl` template: all `types` (comma-separated, or
+/// "*"), never close after a state change, ping every `ping` seconds
+pub fn event_source_url(template: &str, types: &str, ping: u64) -> String {
+    template
+        .replace("{types}", types)
+        .replace("{closeafter}", "no")
+        .replace("{ping}", &ping.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_events() {
+        let mut parser = SseParser::default();
+        let events = parser.feed(
+            b": comment\r\nevent: state\r\ndata: {\"changed\":{}}\r\n\r\nevent: ping\ndata: {}\n\n",
+        );
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    event: "state".into(),
+                    data: "{\"changed\":{}}".into()
+                },
+                SseEvent {
+                    event: "ping".into(),
+                    data: "{}".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_split_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.feed(b"data: first").is_empty());
+        assert!(parser.feed(b"\ndata: sec").is_empty());
+        let events = parser.feed(b"ond\n\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "message");
+        assert_eq!(events[0].data, "first\nsecond");
+    }
+
+    #[test]
+    fn test_event_source_url() {
+        assert_eq!(
+            event_source_url(
+                "https://api.example.com/event/?types={types}&closeafter={closeafter}&ping={ping}",
+                "*",
+                30
+            ),
+            "https://api.example.com/event/?types=*&closeafter=no&ping=30"
+        );
+    }
+}
@@ -1,27 +1,72 @@
-use crate::commands::SearchFilter;
+mod cache;
+mod events;
+mod query;
+mod retry;
+
+use crate::commands::{ComposeOptions, ForwardMode, QueryOptions, SearchFilter};
+use crate::config::Config;
 use crate::error::{Error, Result};
 use crate::models::*;
+use crate::util::{escape_html, infer_mime, safe_file_name, well_known_jmap_url};
+use cache::SessionCache;
+use chrono::{DateTime, SecondsFormat, Utc};
+pub use events::EventStream;
+use query::{Query, normalize_date};
 use reqwest::Client;
+use retry::RetryPolicy;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use std::collections::HashMap;
+use std::path::Path;
 use std::time::Duration;
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, warn};
 
+/// Fastmail's session resource, used unless another server is configured
 const SESSION_URL: &str = "https://api.fastmail.com/jmap/session";
 const TIMEOUT: Duration = Duration::from_secs(30);
 
-const CAPABILITIES: &[&str] = &[
-    "urn:ietf:params:jmap:core",
+/// Properties fetched for email summaries in listings
+const SUMMARY_PROPERTIES: &[&str] = &[
+    "id",
+    "threadId",
+    "mailboxIds",
+    "keywords",
+    "size",
+    "receivedAt",
+    "from",
+    "to",
+    "cc",
+    "subject",
+    "preview",
+    "hasAttachment",
+];
+
+/// Capabilities every request declares; `doctor` checks the token grants each one
+pub const CAPABILITIES: &[&str] = &[
+    CORE_CAPABILITY,
     "urn:ietf:params:jmap:mail",
     "urn:ietf:params:jmap:submission",
-    "https://www.fastmail.com/dev/maskedemail",
+    MASKED_EMAIL_CAPABILITY,
 ];
 
+const CORE_CAPABILITY: &str = "urn:ietf:params:jmap:core";
+
+/// Fastmail-only; other servers reject any request that declares it
+pub const MASKED_EMAIL_CAPABILITY: &str = "https://www.fastmail.com/dev/maskedemail";
+
+/// JMAP Sharing (RFC 9670): mailbox `shareWith`. Only sent when the session advertises it.
+const PRINCIPALS_CAPABILITY: &str = "urn:ietf:params:jmap:principals";
+
 pub struct JmapClient {
     client: Client,
     token: String,
+    session_url: String,
     session: Option<Session>,
+    /// Account name or ID requested with `--account`, resolved on authenticate
+    account: Option<String>,
+    account_id: Option<String>,
+    retry: RetryPolicy,
+    cache: Option<SessionCache>,
 }
 
 #[derive(Debug, Serialize)]
@@ -35,6 +80,8 @@ struct JmapRequest {
 struct JmapResponse {
     #[serde(rename = "methodResponses")]
     method_responses: Vec<Value>,
+    #[serde(rename = "sessionState")]
+    session_state: Option<String>,
 }
 
 impl JmapClient {
@@ -47,18 +94,153 @@ impl JmapClient {
         Self {
             client,
             token,
+            session_url: SESSION_URL.to_string(),
             session: None,
+            account: None,
+            account_id: None,
+            retry: RetryPolicy::default(),
+            cache: None,
+        }
+    }
+
+    /// Retry idempotent requests this many times on 429, 5xx and connection errors
+    pub fn with_retries(mut self, max_retries: u32) -> Self {
+        self.retry = RetryPolicy { max_retries };
+        self
+    }
+
+    /// Send a request, retrying with backoff when `idempotent` and the failure is transient.
+    /// `build` is called again for each attempt.
+    async fn send(
+        &self,
+        idempotent: bool,
+        build: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let mut attempt = 0;
+        loop {
+            let result = build().send().await;
+            // Some(retry_after) when the failure is worth retrying
+            let transient = match &result {
+                Ok(resp) if idempotent && retry::is_retryable(resp.status()) => Some(
+                    resp.headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| retry::parse_retry_after(v, Utc::now())),
+                ),
+                Err(e) if idempotent && (e.is_timeout() || e.is_connect()) => Some(None),
+                _ => None,
+            };
+
+            match transient.and_then(|retry_after| self.retry.delay(attempt, retry_after)) {
+                Some(delay) => {
+                    let reason = match &result {
+                        Ok(resp) => resp.status().to_string(),
+                        Err(e) => e.to_string(),
+                    };
+                    warn!(attempt = attempt + 1, ?delay, %reason, "Retrying request");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return Ok(result?),
+            }
+        }
+    }
+
+    /// Client for the configured token, server, account, retry policy and session cache
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Ok(Self::new(config.get_token()?)
+            .with_session_url(config.get_session_url())
+            .with_account(config.get_account())
+            .with_retries(config.get_max_retries())
+            .with_session_cache(config.session_cache_dir()))
+    }
+
+    /// Talk to another JMAP server (self-hosted, or a test server in CI); None keeps Fastmail.
+    /// Set before `with_session_cache`, which keys the cache by server.
+    pub fn with_session_url(mut self, url: Option<String>) -> Self {
+        if let Some(url) = url {
+            self.session_url = url;
+        }
+        self
+    }
+
+    /// Reuse the session stored in `dir` instead of fetching it, if there is one
+    pub fn with_session_cache(mut self, dir: Option<std::path::PathBuf>) -> Self {
+        self.cache = dir.map(|dir| SessionCache::new(dir, &self.session_url, &self.token));
+        self
+    }
+
+    fn invalidate_cache(&self) {
+        if let Some(ref cache) = self.cache {
+            cache.remove();
+        }
+    }
+
+    /// Act on a shared or delegated account (by name or ID) instead of the primary one
+    pub fn with_account(mut self, account: Option<String>) -> Self {
+        self.account = account;
+        self
+    }
+
+    /// Find the JMAP session URL for an email address or domain via `/.well-known/jmap`
+    /// (RFC 8620 section 2.2). Redirects are followed by hand so the final URL, not the
+    /// well-known one, is what gets saved; a server answering directly (even with 401)
+    /// serves the session there.
+    pub async fn discover_session_url(email_or_domain: &str) -> Result<String> {
+        let mut url = well_known_jmap_url(email_or_domain).ok_or_else(|| {
+            Error::Config(format!(
+                "Not an email address or domain: {}",
+                email_or_domain
+            ))
+        })?;
+        let client = Client::builder()
+            .timeout(TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("Failed to build HTTP client");
+
+        for _ in 0..5 {
+            debug!(%url, "Probing for JMAP session");
+            let resp = client.get(&url).send().await?;
+            let status = resp.status();
+            if status.is_redirection() {
+                let location = resp
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|v| v.to_str().ok())
+                    .ok_or_else(|| Error::Server(format!("{} redirect without Location", url)))?;
+                url = reqwest::Url::parse(&url)
+                    .and_then(|base| base.join(location))
+                    .map_err(|e| Error::Server(format!("Bad redirect from {}: {}", url, e)))?
+                    .to_string();
+                continue;
+            }
+            if status.is_success() || status == reqwest::StatusCode::UNAUTHORIZED {
+                return Ok(url);
+            }
+            return Err(Error::Config(format!(
+                "No JMAP server found for {} ({} from {})",
+                email_or_domain, status, url
+            )));
         }
+        Err(Error::Server(format!(
+            "Too many redirects discovering {}",
+            email_or_domain
+        )))
     }
 
     #[instrument(skip(self))]
     pub async fn authenticate(&mut self) -> Result<&Session> {
-        debug!("Fetching JMAP session");
+        if let Some(session) = self.cache.as_ref().and_then(SessionCache::load) {
+            debug!(username = %session.username, "Using cached session");
+            return self.set_session(session);
+        }
+
+        debug!(url = %self.session_url, "Fetching JMAP session");
         let resp = self
-            .client
-            .get(SESSION_URL)
-            .bearer_auth(&self.token)
-            .send()
+            .send(true, || {
+                self.client.get(&self.session_url).bearer_auth(&self.token)
+            })
             .await?;
 
         match resp.status().as_u16() {
@@ -70,6 +252,22 @@ impl JmapClient {
 
         let session: Session = resp.json().await?;
         debug!(username = %session.username, "Session established");
+        if let Some(ref cache) = self.cache {
+            cache.store(&session);
+        }
+        self.set_session(session)
+    }
+
+    fn set_session(&mut self, session: Session) -> Result<&Session> {
+        self.account_id = match self.account.as_deref() {
+            Some(account) => Some(
+                session
+                    .find_account(account)
+                    .ok_or_else(|| Error::AccountNotFound(account.into()))?
+                    .to_string(),
+            ),
+            None => None,
+        };
         self.session = Some(session);
         Ok(self.session.as_ref().unwrap())
     }
@@ -78,31 +276,122 @@ impl JmapClient {
         self.session.as_ref().ok_or(Error::NotAuthenticated)
     }
 
+    /// The account every method acts on: the one chosen with `with_account`, else the primary mail account
+    pub fn account_id(&self) -> Result<&str> {
+        if let Some(ref id) = self.account_id {
+            return Ok(id);
+        }
+        self.session()?
+            .primary_account_id()
+            .ok_or_else(|| Error::Config("No primary account".into()))
+    }
+
+    /// Fail early with a clear error when the server doesn't offer `capability`
+    fn require_capability(&self, capability: &str) -> Result<()> {
+        if self.session()?.has_capability(capability) {
+            Ok(())
+        } else {
+            Err(Error::MissingCapability(capability.into()))
+        }
+    }
+
+    /// Server limits on request size and objects per call
+    pub fn limits(&self) -> Result<CoreCapabilities> {
+        Ok(self.session()?.core_capabilities())
+    }
+
     #[instrument(skip(self, method_calls))]
     async fn request(&self, method_calls: Vec<Value>) -> Result<Vec<Value>> {
         let session = self.session()?;
+        let limits = session.core_capabilities();
+        if method_calls.len() as u64 > limits.max_calls_in_request {
+            return Err(Error::Jmap {
+                method: "request".into(),
+                error_type: "limit".into(),
+                description: format!(
+                    "{} method calls exceeds the server's maxCallsInRequest ({})",
+                    method_calls.len(),
+                    limits.max_calls_in_request
+                ),
+            });
+        }
+
         let req = JmapRequest {
-            using: CAPABILITIES.iter().map(|s| s.to_string()).collect(),
+            // Declaring a capability the server lacks fails the whole request
+            // (unknownCapability), so only declare what the session advertises
+            using: CAPABILITIES
+                .iter()
+                .copied()
+                .chain(Some(PRINCIPALS_CAPABILITY))
+                .filter(|c| *c == CORE_CAPABILITY || session.has_capability(c))
+                .map(String::from)
+                .collect(),
             method_calls,
         };
 
-        debug!(url = %session.api_url, "Making JMAP request");
+        let body = serde_json::to_vec(&req)?;
+        if body.len() as u64 > limits.max_size_request {
+            return Err(Error::Jmap {
+                method: "request".into(),
+                error_type: "requestTooLarge".into(),
+                description: format!(
+                    "Request of {} bytes exceeds the server's maxSizeRequest ({})",
+                    body.len(),
+                    limits.max_size_request
+                ),
+            });
+        }
+
+        debug!(url = %session.api_url, size = body.len(), "Making JMAP request");
+        let idempotent = retry::is_idempotent(&req.method_calls);
         let resp = self
-            .client
-            .post(&session.api_url)
-            .bearer_auth(&self.token)
-            .json(&req)
-            .send()
+            .send(idempotent, || {
+                self.client
+                    .post(&session.api_url)
+                    .bearer_auth(&self.token)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body.clone())
+            })
             .await?;
 
         match resp.status().as_u16() {
-            401 => return Err(Error::InvalidToken("Token expired or invalid".into())),
+            401 => {
+                self.invalidate_cache();
+                return Err(Error::InvalidToken("Token expired or invalid".into()));
+            }
+            400 => {
+                // Request-level error (RFC 8620 §3.6.1), e.g. unknownCapability
+                // when a cached session no longer matches the server
+                let problem: Value = resp.json().await.unwrap_or(Value::Null);
+                let error_type = problem
+                    .get("type")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("unknown")
+                    .trim_start_matches("urn:ietf:params:jmap:error:")
+                    .to_string();
+                if error_type == "unknownCapability" {
+                    self.invalidate_cache();
+                }
+                return Err(Error::Jmap {
+                    method: "request".into(),
+                    error_type,
+                    description: problem
+                        .get("detail")
+                        .and_then(|v| v.as_str())
+                        .unwrap_or("Request rejected")
+                        .to_string(),
+                });
+            }
             429 => return Err(Error::RateLimited),
             500..=599 => return Err(Error::Server(format!("Server error: {}", resp.status()))),
             _ => {}
         }
 
         let jmap_resp: JmapResponse = resp.json().await?;
+        if jmap_resp.session_state.is_some() && jmap_resp.session_state != session.state {
+            debug!("Session state changed");
+            self.invalidate_cache();
+        }
         Ok(jmap_resp.method_responses)
     }
 
@@ -150,21 +439,31 @@ impl JmapClient {
 
     #[instrument(skip(self))]
     pub async fn list_mailboxes(&self) -> Result<Vec<Mailbox>> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        let account_id = self.account_id()?;
+
+        let mut properties = vec![
+            "id",
+            "name",
+            "parentId",
+            "role",
+            "totalEmails",
+            "unreadEmails",
+            "totalThreads",
+            "unreadThreads",
+            "sortOrder",
+            "isSubscribed",
+            "myRights",
+        ];
+        if self.session()?.has_capability(PRINCIPALS_CAPABILITY) {
+            properties.extend(["shareWith", "isSeenShared"]);
+        }
 
         let responses = self
             .request(vec![json!([
                 "Mailbox/get",
                 {
                     "accountId": account_id,
-                    "properties": [
-                        "id", "name", "parentId", "role",
-                        "totalEmails", "unreadEmails",
-                        "totalThreads", "unreadThreads", "sortOrder"
-                    ]
+                    "properties": properties
                 },
                 "m0"
             ])])
@@ -181,83 +480,274 @@ impl JmapClient {
         Ok(resp.list)
     }
 
+    /// Find a mailbox by path, role or name (see `Mailbox::resolve`)
     pub async fn find_mailbox(&self, name: &str) -> Result<Mailbox> {
         let mailboxes = self.list_mailboxes().await?;
-        let name_lower = name.to_lowercase();
+        Mailbox::resolve(&mailboxes, name).cloned()
+    }
 
-        if let Some(m) = mailboxes
-            .iter()
-            .find(|m| m.name.to_lowercase() == name_lower)
-        {
-            return Ok(m.clone());
+    /// Create a mailbox, optionally under a parent, returning its ID
+    #[instrument(skip(self))]
+    pub async fn create_mailbox(&self, name: &str, parent_id: Option<&str>) -> Result<String> {
+        let account_id = self.account_id()?;
+
+        let responses = self
+            .request(vec![json!([
+                "Mailbox/set",
+                {
+                    "accountId": account_id,
+                    "create": {
+                        "mailbox": {
+                            "name": name,
+                            "parentId": parent_id
+                        }
+                    }
+                },
+                "m0"
+            ])])
+            .await?;
+
+        #[derive(Deserialize)]
+        struct SetResponse {
+            created: Option<HashMap<String, Value>>,
+            #[serde(rename = "notCreated")]
+            not_created: Option<HashMap<String, Value>>,
         }
 
-        if let Some(m) = mailboxes
-            .iter()
-            .find(|m| m.role.as_deref().map(|r: &str| r.to_lowercase()) == Some(name_lower.clone()))
-        {
-            return Ok(m.clone());
+        let resp: SetResponse =
+            Self::parse_response(responses.first().unwrap_or(&Value::Null), "Mailbox/set")?;
+
+        if let Some(err) = resp.not_created.as_ref().and_then(|m| m.get("mailbox")) {
+            return Err(set_error("Mailbox/set", err, "Failed to create mailbox"));
         }
 
-        Err(Error::MailboxNotFound(name.into()))
+        resp.created
+            .as_ref()
+            .and_then(|m| m.get("mailbox"))
+            .and_then(|v| v.get("id"))
+            .and_then(|v| v.as_str())
+            .map(String::from)
+            .ok_or_else(|| Error::Jmap {
+                method: "Mailbox/set".into(),
+                error_type: "unknown".into(),
+                description: "Failed to create mailbox".into(),
+            })
     }
 
+    /// Apply a Mailbox/set patch, e.g. a new `name` or `parentId`
     #[instrument(skip(self))]
-    pub async fn list_emails(&self, mailbox_id: &str, limit: u32) -> Result<Vec<Email>> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+    pub async fn update_mailbox(&self, mailbox_id: &str, patch: Value) -> Result<()> {
+        let account_id = self.account_id()?;
 
         let responses = self
-            .request(vec![
-                json!([
-                    "Email/query",
-                    {
-                        "accountId": account_id,
-                        "filter": { "inMailbox": mailbox_id },
-                        "sort": [{"property": "receivedAt", "isAscending": false}],
-                        "limit": limit
-                    },
-                    "q0"
-                ]),
-                json!([
-                    "Email/get",
-                    {
-                        "accountId": account_id,
-                        "#ids": {
-                            "resultOf": "q0",
-                            "name": "Email/query",
-                            "path": "/ids"
-                        },
-                        "properties": [
-                            "id", "threadId", "mailboxIds", "keywords",
-                            "size", "receivedAt", "from", "to", "cc",
-                            "subject", "preview", "hasAttachment"
-                        ]
-                    },
-                    "g0"
-                ]),
-            ])
+            .request(vec![json!([
+                "Mailbox/set",
+                {
+                    "accountId": account_id,
+                    "update": { (mailbox_id): patch }
+                },
+                "m0"
+            ])])
+            .await?;
+
+        #[derive(Deserialize)]
+        struct SetResponse {
+            #[serde(rename = "notUpdated")]
+            not_updated: Option<HashMap<String, Value>>,
+        }
+
+        let resp: SetResponse =
+            Self::parse_response(responses.first().unwrap_or(&Value::Null), "Mailbox/set")?;
+
+        if let Some(err) = resp.not_updated.as_ref().and_then(|m| m.get(mailbox_id)) {
+            return Err(set_error("Mailbox/set", err, "Failed to update mailbox"));
+        }
+
+        Ok(())
+    }
+
+    /// Destroy a mailbox. Unless `remove_emails` is set the server refuses
+    /// (`mailboxHasEmail`) when the mailbox isn't empty; with it, emails only
+    /// in this mailbox are destroyed and the rest just lose this mailbox.
+    #[instrument(skip(self))]
+    pub async fn destroy_mailbox(&self, mailbox_id: &str, remove_emails: bool) -> Result<()> {
+        let account_id = self.account_id()?;
+
+        let responses = self
+            .request(vec![json!([
+                "Mailbox/set",
+                {
+                    "accountId": account_id,
+                    "destroy": [mailbox_id],
+                    "onDestroyRemoveEmails": remove_emails
+                },
+                "m0"
+            ])])
             .await?;
 
+        #[derive(Deserialize)]
+        struct SetResponse {
+            #[serde(rename = "notDestroyed")]
+            not_destroyed: Option<HashMap<String, Value>>,
+        }
+
+        let resp: SetResponse =
+            Self::parse_response(responses.first().unwrap_or(&Value::Null), "Mailbox/set")?;
+
+        if let Some(err) = resp.not_destroyed.as_ref().and_then(|m| m.get(mailbox_id)) {
+            return Err(set_error("Mailbox/set", err, "Failed to delete mailbox"));
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn list_emails(&self, mailbox_id: &str, page: &QueryOptions) -> Result<EmailPage> {
+        self.query_emails(json!({ "inMailbox": mailbox_id }), page)
+            .await
+    }
+
+    /// Run an Email/query for a single page
+    async fn query_emails(&self, filter: Value, page: &QueryOptions) -> Result<EmailPage> {
+        match self.query_page(&filter, page).await {
+            // The page token is the last email of the previous page; it may have moved since
+            Err(Error::Jmap {
+                method, error_type, ..
+            }) if error_type == "anchorNotFound" => Err(Error::Jmap {
+                method,
+                error_type,
+                description: "The email this page token points at was moved or deleted; \
+                              start over or page with --offset"
+                    .into(),
+            }),
+            result => result,
+        }
+    }
+
+    /// Run an Email/query page by page, handing each page to `on_page` as it arrives
+    /// (`--all`), so nothing is held beyond one page. Pages continue from the last
+    /// email's ID; if that email is moved or deleted meanwhile, paging falls back to
+    /// the position reached so far.
+    pub async fn query_all(
+        &self,
+        filter: &Value,
+        page: &QueryOptions,
+        mut on_page: impl FnMut(&EmailPage) -> Result<()>,
+    ) -> Result<()> {
+        let mut next = page.clone();
+        loop {
+            let result = match self.query_page(filter, &next).await {
+                Err(Error::Jmap { ref error_type, .. })
+                    if error_type == "anchorNotFound" && next.page_token.is_some() =>
+                {
+                    debug!(
+                        offset = next.offset,
+                        "Page anchor gone; continuing by position"
+                    );
+                    next.page_token = None;
+                    self.query_page(filter, &next).await?
+                }
+                result => result?,
+            };
+            debug!(position = result.position, total = ?result.total, "Fetched page");
+            on_page(&result)?;
+
+            let Some(token) = result.next_page_token else {
+                return Ok(());
+            };
+            next.offset = (result.position + result.emails.len() as u64) as u32;
+            next.page_token = Some(token);
+        }
+    }
+
+    /// Fetch a single page of Email/query results with their summaries
+    async fn query_page(&self, filter: &Value, page: &QueryOptions) -> Result<EmailPage> {
+        let account_id = self.account_id()?;
+
+        let mut query = json!({
+            "accountId": account_id,
+            "filter": filter,
+            "sort": [{"property": page.sort, "isAscending": page.ascending}],
+            "collapseThreads": page.collapse_threads,
+            "limit": page.limit,
+            "calculateTotal": true
+        });
+        // A page token is the ID of the last email on the previous page
+        if let Some(ref anchor) = page.page_token {
+            query["anchor"] = json!(anchor);
+            query["anchorOffset"] = json!(1);
+        } else if page.offset > 0 {
+            query["position"] = json!(page.offset);
+        }
+
+        let properties = json!(SUMMARY_PROPERTIES);
+
+        #[derive(Deserialize)]
+        struct EmailQueryResponse {
+            ids: Vec<String>,
+            position: u64,
+            total: Option<u64>,
+        }
+
         #[derive(Deserialize)]
         struct EmailGetResponse {
             list: Vec<Email>,
         }
 
-        let resp: EmailGetResponse =
-            Self::parse_response(responses.get(1).unwrap_or(&Value::Null), "Email/get")?;
+        // Fetch in the same round trip when the page fits in one Email/get
+        let (query, emails) = if page.limit as u64 <= self.limits()?.max_objects_in_get {
+            let responses = self
+                .request(vec![
+                    json!(["Email/query", query, "q0"]),
+                    json!([
+                        "Email/get",
+                        {
+                            "accountId": account_id,
+                            "#ids": {
+                                "resultOf": "q0",
+                                "name": "Email/query",
+                                "path": "/ids"
+                            },
+                            "properties": properties
+                        },
+                        "g0"
+                    ]),
+                ])
+                .await?;
+
+            let query: EmailQueryResponse =
+                Self::parse_response(responses.first().unwrap_or(&Value::Null), "Email/query")?;
+            let resp: EmailGetResponse =
+                Self::parse_response(responses.get(1).unwrap_or(&Value::Null), "Email/get")?;
+            (query, resp.list)
+        } else {
+            let responses = self
+                .request(vec![json!(["Email/query", query, "q0"])])
+                .await?;
+            let query: EmailQueryResponse =
+                Self::parse_response(responses.first().unwrap_or(&Value::Null), "Email/query")?;
+            let emails = self
+                .get_emails(&query.ids, json!({ "properties": properties }))
+                .await?;
+            (query, emails)
+        };
 
-        Ok(resp.list)
+        let more = match query.total {
+            Some(total) => query.position + (query.ids.len() as u64) < total,
+            None => query.ids.len() as u64 >= page.limit as u64,
+        };
+
+        Ok(EmailPage {
+            next_page_token: more.then(|| query.ids.last().cloned()).flatten(),
+            position: query.position,
+            total: query.total,
+            emails,
+        })
     }
 
     #[instrument(skip(self))]
     pub async fn get_email(&self, email_id: &str) -> Result<Email> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        let account_id = self.account_id()?;
 
         let responses = self
             .request(vec![json!([
@@ -302,10 +792,7 @@ impl JmapClient {
     /// Get all emails in a thread
     #[instrument(skip(self))]
     pub async fn get_thread(&self, email_id: &str) -> Result<Vec<Email>> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        let account_id = self.account_id()?;
 
         // First get the email to find its threadId
         let email = self.get_email(email_id).await?;
@@ -346,32 +833,64 @@ impl JmapClient {
             .ok_or_else(|| Error::Config("Thread not found".into()))?;
 
         // Now get all emails in the thread
-        let responses = self
-            .request(vec![json!([
-                "Email/get",
-                {
-                    "accountId": account_id,
-                    "ids": thread.email_ids,
-                    "properties": [
-                        "id", "threadId", "mailboxIds", "keywords",
-                        "size", "receivedAt", "from", "to", "cc",
-                        "subject", "preview", "hasAttachment", "bodyValues"
-                    ],
-                    "fetchTextBodyValues": true
-                },
-                "e0"
-            ])])
-            .await?;
+        self.get_emails(
+            &thread.email_ids,
+            json!({
+                "properties": [
+                    "id", "threadId", "mailboxIds", "keywords",
+                    "size", "receivedAt", "from", "to", "cc",
+                    "subject", "preview", "hasAttachment", "bodyValues"
+                ],
+                "fetchTextBodyValues": true
+            }),
+        )
+        .await
+    }
+
+    /// Summaries (as in listings) of the given emails; IDs that no longer exist are skipped
+    #[instrument(skip(self, ids))]
+    pub async fn get_email_summaries(&self, ids: &[String]) -> Result<Vec<Email>> {
+        self.get_emails(ids, json!({ "properties": SUMMARY_PROPERTIES }))
+            .await
+    }
+
+    /// Email/get for any number of IDs, split into calls of at most
+    /// maxObjectsInGet IDs and requests of at most maxCallsInRequest calls.
+    /// `args` holds everything but `accountId` and `ids`.
+    async fn get_emails(&self, ids: &[String], args: Value) -> Result<Vec<Email>> {
+        let account_id = self.account_id()?;
+        let limits = self.limits()?;
+
+        let calls: Vec<Value> = ids
+            .chunks(limits.max_objects_in_get.max(1) as usize)
+            .enumerate()
+            .map(|(i, chunk)| {
+                let mut call = args.clone();
+                call["accountId"] = json!(account_id);
+                call["ids"] = json!(chunk);
+                json!(["Email/get", call, format!("e{}", i)])
+            })
+            .collect();
 
         #[derive(Deserialize)]
         struct EmailGetResponse {
             list: Vec<Email>,
         }
 
-        let resp: EmailGetResponse =
-            Self::parse_response(responses.first().unwrap_or(&Value::Null), "Email/get")?;
-
-        Ok(resp.list)
+        let mut emails = Vec::with_capacity(ids.len());
+        for batch in calls.chunks(limits.max_calls_in_request.max(1) as usize) {
+            let responses = self.request(batch.to_vec()).await?;
+            for response in &responses {
+                let resp: EmailGetResponse = Self::parse_response(response, "Email/get")?;
+                emails.extend(resp.list);
+            }
+            debug!(
+                fetched = emails.len(),
+                total = ids.len(),
+                "Fetched email batch"
+            );
+        }
+        Ok(emails)
     }
 
     /// Search emails with full JMAP filter support
@@ -380,14 +899,18 @@ impl JmapClient {
         &self,
         filter: &SearchFilter,
         mailbox_id: Option<&str>,
-        limit: u32,
-    ) -> Result<Vec<Email>> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        page: &QueryOptions,
+    ) -> Result<EmailPage> {
+        let jmap_filter = self.search_filter(filter, mailbox_id).await?;
+        self.query_emails(jmap_filter, page).await
+    }
 
-        // Build JMAP filter object
+    /// Build the Email/query FilterCondition for a search, compiling any query expression
+    pub async fn search_filter(
+        &self,
+        filter: &SearchFilter,
+        mailbox_id: Option<&str>,
+    ) -> Result<Value> {
         let mut jmap_filter = json!({});
 
         if let Some(ref text) = filter.text {
@@ -424,21 +947,10 @@ impl JmapClient {
             jmap_filter["maxSize"] = json!(max_size);
         }
         if let Some(ref before) = filter.before {
-            // Normalize date to ISO 8601 if needed
-            let date = if before.contains('T') {
-                before.clone()
-            } else {
-                format!("{}T00:00:00Z", before)
-            };
-            jmap_filter["before"] = json!(date);
+            jmap_filter["before"] = json!(normalize_date(before));
         }
         if let Some(ref after) = filter.after {
-            let date = if after.contains('T') {
-                after.clone()
-            } else {
-                format!("{}T00:00:00Z", after)
-            };
-            jmap_filter["after"] = json!(date);
+            jmap_filter["after"] = json!(normalize_date(after));
         }
         if filter.unread {
             jmap_filter["notKeyword"] = json!("$seen");
@@ -447,55 +959,237 @@ impl JmapClient {
             jmap_filter["hasKeyword"] = json!("$flagged");
         }
 
-        let responses = self
-            .request(vec![
-                json!([
-                    "Email/query",
-                    {
-                        "accountId": account_id,
-                        "filter": jmap_filter,
-                        "sort": [{"property": "receivedAt", "isAscending": false}],
-                        "limit": limit
-                    },
-                    "q0"
-                ]),
-                json!([
-                    "Email/get",
-                    {
-                        "accountId": account_id,
-                        "#ids": {
-                            "resultOf": "q0",
-                            "name": "Email/query",
-                            "path": "/ids"
-                        },
-                        "properties": [
-                            "id", "threadId", "mailboxIds", "keywords",
-                            "size", "receivedAt", "from", "to", "cc",
-                            "subject", "preview", "hasAttachment"
-                        ]
+        // AND the query expression with any flag-based conditions
+        if let Some(ref expression) = filter.query {
+            let compiled = self.compile_query(expression).await?;
+            jmap_filter = if jmap_filter.as_object().is_some_and(|o| o.is_empty()) {
+                compiled
+            } else {
+                json!({ "operator": "AND", "conditions": [jmap_filter, compiled] })
+            };
+        }
+
+        Ok(jmap_filter)
+    }
+
+    /// IDs of emails received after `since` that match `filter` (from `search_filter`)
+    #[instrument(skip(self, filter))]
+    pub async fn email_ids_since(
+        &self,
+        filter: &Value,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<String>> {
+        let after = json!({ "after": since.to_rfc3339_opts(SecondsFormat::Secs, true) });
+        self.query_ids(json!({ "operator": "AND", "conditions": [filter, after] }))
+            .await
+    }
+
+    /// Parse a query expression and resolve its `in:` mailboxes into a JMAP filter
+    async fn compile_query(&self, expression: &str) -> Result<Value> {
+        let query = Query::parse(expression)?;
+        let mut mailbox_ids = HashMap::new();
+        for name in query.mailboxes() {
+            let mailbox = self.find_mailbox(name).await?;
+            mailbox_ids.insert(name.to_string(), mailbox.id);
+        }
+        query.to_filter(&mailbox_ids)
+    }
+
+    /// IDs of every email matching a query expression, for bulk operations
+    #[instrument(skip(self))]
+    pub async fn query_email_ids(&self, expression: &str) -> Result<Vec<String>> {
+        let filter = self.compile_query(expression).await?;
+        self.query_ids(filter).await
+    }
+
+    /// IDs of every email in a mailbox
+    #[instrument(skip(self))]
+    pub async fn mailbox_email_ids(&self, mailbox_id: &str) -> Result<Vec<String>> {
+        self.query_ids(json!({ "inMailbox": mailbox_id })).await
+    }
+
+    /// Sort a mailbox's emails into those only in it and those also filed elsewhere,
+    /// so emptying it never deletes mail from other mailboxes
+    #[instrument(skip(self))]
+    pub async fn plan_empty_mailbox(&self, mailbox_id: &str) -> Result<EmptyPlan> {
+        let ids = self.mailbox_email_ids(mailbox_id).await?;
+        let emails = self
+            .get_emails(&ids, json!({ "properties": ["id", "mailboxIds"] }))
+            .await?;
+        Ok(EmptyPlan::new(&emails, mailbox_id))
+    }
+
+    /// Carry out an `EmptyPlan`: destroy emails only in the mailbox and take the rest out of it
+    #[instrument(skip(self, plan))]
+    pub async fn empty_mailbox(&self, mailbox_id: &str, plan: &EmptyPlan) -> Result<BulkResult> {
+        let mut result = self.destroy_emails(&plan.destroy).await?;
+        let removed = self
+            .update_emails(
+                &plan.remove,
+                json!({ format!("mailboxIds/{}", mailbox_id): null }),
+            )
+            .await?;
+        result.succeeded.extend(removed.succeeded);
+        result.failed.extend(removed.failed);
+        Ok(result)
+    }
+
+    /// Page through an Email/query collecting every matching ID
+    async fn query_ids(&self, filter: Value) -> Result<Vec<String>> {
+        let account_id = self.account_id()?;
+
+        #[derive(Deserialize)]
+        struct EmailQueryResponse {
+            ids: Vec<String>,
+            total: Option<u64>,
+        }
+
+        let page_size = self.limits()?.max_objects_in_get;
+        let mut ids: Vec<String> = Vec::new();
+        loop {
+            let responses = self
+                .request(vec![json!([
+                    "Email/query",
+                    {
+                        "accountId": account_id,
+                        "filter": filter,
+                        "sort": [{"property": "receivedAt", "isAscending": false}],
+                        "position": ids.len(),
+                        "limit": page_size,
+                        "calculateTotal": true
                     },
-                    "g0"
-                ]),
-            ])
+                    "q0"
+                ])])
+                .await?;
+
+            let resp: EmailQueryResponse =
+                Self::parse_response(responses.first().unwrap_or(&Value::Null), "Email/query")?;
+            // The server may return fewer than `limit`, so only stop on an empty page or the total
+            let done = resp.ids.is_empty()
+                || resp
+                    .total
+                    .is_some_and(|t| (ids.len() + resp.ids.len()) as u64 >= t);
+            ids.extend(resp.ids);
+            if done {
+                return Ok(ids);
+            }
+        }
+    }
+
+    /// Open the push stream for every data type, asking for a ping every `ping` interval
+    #[instrument(skip(self))]
+    pub async fn event_source(&self, ping: Duration) -> Result<EventStream> {
+        let template =
+            self.session()?.event_source_url.as_deref().ok_or_else(|| {
+                Error::Config("Server doesn't offer push (no eventSourceUrl)".into())
+            })?;
+        let url = events::event_source_url(template, "*", ping.as_secs());
+
+        // No overall timeout: the response never ends. Silence is caught by EventStream.
+        let client = Client::builder()
+            .connect_timeout(TIMEOUT)
+            .build()
+            .expect("Failed to build HTTP client");
+        let resp = client
+            .get(&url)
+            .bearer_auth(&self.token)
+            .header(reqwest::header::ACCEPT, "text/event-stream")
+            .send()
+            .await?;
+
+        match resp.status().as_u16() {
+            401 => {
+                self.invalidate_cache();
+                return Err(Error::InvalidToken("Authentication failed".into()));
+            }
+            200..=299 => {}
+            429 => return Err(Error::RateLimited),
+            // Forbidden, not found and the like won't fix themselves by reconnecting
+            400..=499 if resp.status() != reqwest::StatusCode::REQUEST_TIMEOUT => {
+                return Err(Error::Config(format!(
+                    "Push stream rejected: {} from {}",
+                    resp.status(),
+                    url
+                )));
+            }
+            _ => return Err(Error::Server(format!("Push stream: {}", resp.status()))),
+        }
+        debug!(%url, "Push stream open");
+        // Allow two missed pings before treating the connection as dead
+        Ok(EventStream::new(resp, ping * 2 + TIMEOUT))
+    }
+
+    /// Current state string for `data_type` ("Email", "Mailbox" or "Thread"), the
+    /// starting point for `changes`
+    #[instrument(skip(self))]
+    pub async fn current_state(&self, data_type: &str) -> Result<String> {
+        let method = format!("{}/get", data_type);
+        let responses = self
+            .request(vec![json!([
+                method,
+                {
+                    "accountId": self.account_id()?,
+                    "ids": [],
+                    "properties": ["id"]
+                },
+                "s0"
+            ])])
             .await?;
 
         #[derive(Deserialize)]
-        struct EmailGetResponse {
-            list: Vec<Email>,
+        struct StateResponse {
+            state: String,
         }
 
-        let resp: EmailGetResponse =
-            Self::parse_response(responses.get(1).unwrap_or(&Value::Null), "Email/get")?;
+        let resp: StateResponse =
+            Self::parse_response(responses.first().unwrap_or(&Value::Null), &method)?;
+        Ok(resp.state)
+    }
 
-        Ok(resp.list)
+    /// IDs of `data_type` objects created, updated or destroyed since `since_state`,
+    /// following `hasMoreChanges` to the latest state. A state the server can no longer
+    /// diff from fails with `cannotCalculateChanges`.
+    #[instrument(skip(self))]
+    pub async fn changes(&self, data_type: &str, since_state: &str) -> Result<Changes> {
+        let account_id = self.account_id()?;
+        let method = format!("{}/changes", data_type);
+        let max_changes = self.limits()?.max_objects_in_get;
+
+        let mut merged: Option<Changes> = None;
+        loop {
+            let state = merged
+                .as_ref()
+                .map_or(since_state, |c| c.new_state.as_str())
+                .to_string();
+            let responses = self
+                .request(vec![json!([
+                    method,
+                    {
+                        "accountId": account_id,
+                        "sinceState": state,
+                        "maxChanges": max_changes
+                    },
+                    "c0"
+                ])])
+                .await?;
+
+            let page: Changes =
+                Self::parse_response(responses.first().unwrap_or(&Value::Null), &method)?;
+            // A server that claims more changes without advancing would loop forever
+            let more = page.has_more_changes && page.new_state != state;
+            match merged {
+                Some(ref mut changes) => changes.merge(page),
+                None => merged = Some(page),
+            }
+            if !more {
+                return Ok(merged.unwrap());
+            }
+        }
     }
 
     #[instrument(skip(self))]
     pub async fn list_identities(&self) -> Result<Vec<Identity>> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        let account_id = self.account_id()?;
 
         let responses = self
             .request(vec![json!([
@@ -516,7 +1210,41 @@ impl JmapClient {
         Ok(resp.list)
     }
 
-    #[instrument(skip(self, body))]
+    /// Resolve the sending identity from an identity ID or address, defaulting to the first
+    pub async fn find_identity(&self, from: Option<&str>) -> Result<Identity> {
+        let identities = self.list_identities().await?;
+        let Some(from) = from else {
+            return identities.into_iter().next().ok_or(Error::IdentityNotFound);
+        };
+        Identity::find(&identities, from)
+    }
+
+    /// Identity to compose with: the one the caller already resolved, else `options.from`
+    async fn compose_identity(&self, options: &ComposeOptions) -> Result<Identity> {
+        match options.identity {
+            Some(ref identity) => Ok(identity.clone()),
+            None => self.find_identity(options.from.as_deref()).await,
+        }
+    }
+
+    /// Identity for replying to `original`: an explicit `from`, else the identity the
+    /// original was addressed to, else the default
+    pub async fn reply_identity(&self, original: &Email, from: Option<&str>) -> Result<Identity> {
+        if from.is_some() {
+            return self.find_identity(from).await;
+        }
+        let identities = self.list_identities().await?;
+        let recipients = [&original.to, &original.cc, &original.bcc]
+            .into_iter()
+            .flatten()
+            .flatten();
+        Identity::select(&identities, recipients)
+            .or_else(|| identities.into_iter().next())
+            .ok_or(Error::IdentityNotFound)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip(self, body, options))]
     pub async fn send_email(
         &self,
         to: Vec<EmailAddress>,
@@ -525,14 +1253,12 @@ impl JmapClient {
         subject: &str,
         body: &str,
         in_reply_to: Option<&str>,
+        options: &ComposeOptions,
     ) -> Result<String> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        let account_id = self.account_id()?;
 
-        let identities = self.list_identities().await?;
-        let identity = identities.first().ok_or(Error::IdentityNotFound)?;
+        let identity = self.compose_identity(options).await?;
+        let (body, html) = compose_body(&identity, body, options);
 
         let sent = self.find_mailbox("sent").await?;
 
@@ -543,47 +1269,55 @@ impl JmapClient {
             "from".into(),
             json!([{ "email": identity.email, "name": identity.name }]),
         );
-        email_create.insert(
-            "to".into(),
-            json!(
-                to.iter()
-                    .map(|a| json!({"email": a.email, "name": a.name}))
-                    .collect::<Vec<_>>()
-            ),
-        );
+        email_create.insert("to".into(), address_list(&to));
         if !cc.is_empty() {
-            email_create.insert(
-                "cc".into(),
-                json!(
-                    cc.iter()
-                        .map(|a| json!({"email": a.email, "name": a.name}))
-                        .collect::<Vec<_>>()
-                ),
-            );
+            email_create.insert("cc".into(), address_list(&cc));
         }
         if !bcc.is_empty() {
-            email_create.insert(
-                "bcc".into(),
-                json!(
-                    bcc.iter()
-                        .map(|a| json!({"email": a.email, "name": a.name}))
-                        .collect::<Vec<_>>()
-                ),
-            );
+            email_create.insert("bcc".into(), address_list(&bcc));
         }
         email_create.insert("subject".into(), json!(subject));
-        email_create.insert(
-            "bodyValues".into(),
-            json!({ "body": { "value": body, "charset": "utf-8" } }),
-        );
-        email_create.insert(
-            "textBody".into(),
-            json!([{ "partId": "body", "type": "text/plain" }]),
-        );
+        insert_body(&mut email_create, &body, html.as_deref());
+        if !options.attachments.is_empty() {
+            email_create.insert("attachments".into(), attachment_list(&options.attachments));
+        }
         if let Some(reply_id) = in_reply_to {
             email_create.insert("inReplyTo".into(), json!([reply_id]));
         }
 
+        let email_id = self
+            .create_and_submit(account_id, &identity, email_create, options.send_at)
+            .await?;
+
+        debug!(email_id = %email_id, "Email sent successfully");
+        Ok(email_id)
+    }
+
+    /// Create an email and submit it for delivery in a single request, holding it on the
+    /// server until `send_at` if given
+    async fn create_and_submit(
+        &self,
+        account_id: &str,
+        identity: &Identity,
+        email_create: HashMap<String, Value>,
+        send_at: Option<DateTime<Utc>>,
+    ) -> Result<String> {
+        let mut submission = json!({
+            "identityId": identity.id,
+            "emailId": "#email"
+        });
+        if let Some(send_at) = send_at {
+            self.check_send_at(send_at)?;
+            let recipients: Vec<String> = ["to", "cc", "bcc"]
+                .iter()
+                .filter_map(|field| email_create.get(*field).and_then(|v| v.as_array()))
+                .flatten()
+                .filter_map(|addr| addr.get("email").and_then(|e| e.as_str()))
+                .map(String::from)
+                .collect();
+            submission["envelope"] = envelope(&identity.email, &recipients, send_at);
+        }
+
         let responses = self
             .request(vec![
                 json!([
@@ -598,12 +1332,7 @@ impl JmapClient {
                     "EmailSubmission/set",
                     {
                         "accountId": account_id,
-                        "create": {
-                            "submission": {
-                                "identityId": identity.id,
-                                "emailId": "#email"
-                            }
-                        },
+                        "create": { "submission": submission },
                         "onSuccessUpdateEmail": {
                             "#submission": {
                                 "keywords/$seen": true
@@ -657,16 +1386,435 @@ impl JmapClient {
                 description: "No email ID returned".into(),
             })?;
 
-        debug!(email_id = %email_id, "Email sent successfully");
+        #[derive(Deserialize)]
+        struct SubmissionSetResponse {
+            #[serde(rename = "notCreated")]
+            not_created: Option<HashMap<String, Value>>,
+        }
+
+        // The email exists even when its submission is rejected (e.g. a HOLDUNTIL beyond
+        // maxDelayedSend, forbiddenFrom or tooManyRecipients)
+        let submitted = Self::parse_response::<SubmissionSetResponse>(
+            responses.get(1).unwrap_or(&Value::Null),
+            "EmailSubmission/set",
+        )
+        .and_then(|resp| {
+            match resp.not_created.as_ref().and_then(|m| m.get("submission")) {
+                Some(err) => Err(set_error(
+                    "EmailSubmission/set",
+                    err,
+                    "Failed to send email",
+                )),
+                None => Ok(()),
+            }
+        });
+        if let Err(e) = submitted {
+            // Don't leave an unsent email in Sent looking delivered
+            let removed = self
+                .destroy_emails(std::slice::from_ref(&email_id))
+                .await
+                .is_ok_and(|r| r.failed.is_empty());
+            return Err(match e {
+                Error::Jmap {
+                    method,
+                    error_type,
+                    description,
+                } if !removed => Error::Jmap {
+                    method,
+                    error_type,
+                    description: format!(
+                        "{}; the unsent email {} could not be removed from Sent",
+                        description, email_id
+                    ),
+                },
+                e => e,
+            });
+        }
+
         Ok(email_id)
     }
 
+    /// Save a draft in the Drafts mailbox, replacing `replaces` (and keeping its threading
+    /// headers) when editing an existing draft
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip(self, body, options, replaces))]
+    pub async fn save_draft(
+        &self,
+        to: Vec<EmailAddress>,
+        cc: Vec<EmailAddress>,
+        bcc: Vec<EmailAddress>,
+        subject: &str,
+        body: &str,
+        options: &ComposeOptions,
+        replaces: Option<&Email>,
+    ) -> Result<String> {
+        let account_id = self.account_id()?;
+
+        let identity = self.compose_identity(options).await?;
+        let (body, html) = compose_body(&identity, body, options);
+
+        let drafts = self.find_mailbox("drafts").await?;
+
+        let mut email_create: HashMap<String, Value> = HashMap::new();
+        email_create.insert("mailboxIds".into(), json!({ drafts.id.clone(): true }));
+        email_create.insert("keywords".into(), json!({ "$draft": true, "$seen": true }));
+        email_create.insert(
+            "from".into(),
+            json!([{ "email": identity.email, "name": identity.name }]),
+        );
+        email_create.insert("to".into(), address_list(&to));
+        if !cc.is_empty() {
+            email_create.insert("cc".into(), address_list(&cc));
+        }
+        if !bcc.is_empty() {
+            email_create.insert("bcc".into(), address_list(&bcc));
+        }
+        email_create.insert("subject".into(), json!(subject));
+        insert_body(&mut email_create, &body, html.as_deref());
+        if !options.attachments.is_empty() {
+            email_create.insert("attachments".into(), attachment_list(&options.attachments));
+        }
+        if let Some(old) = replaces {
+            if let Some(ref in_reply_to) = old.in_reply_to {
+                email_create.insert("inReplyTo".into(), json!(in_reply_to));
+            }
+            if let Some(ref references) = old.references {
+                email_create.insert("references".into(), json!(references));
+            }
+        }
+
+        let destroy: Vec<&str> = replaces.map(|e| e.id.as_str()).into_iter().collect();
+        let responses = self
+            .request(vec![json!([
+                "Email/set",
+                {
+                    "accountId": account_id,
+                    "create": { "draft": email_create },
+                    "destroy": destroy
+                },
+                "d0"
+            ])])
+            .await?;
+
+        #[derive(Deserialize)]
+        struct EmailSetResponse {
+            created: Option<HashMap<String, Value>>,
+            #[serde(rename = "notCreated")]
+            not_created: Option<HashMap<String, Value>>,
+            #[serde(rename = "notDestroyed")]
+            not_destroyed: Option<HashMap<String, Value>>,
+        }
+
+        let resp: EmailSetResponse =
+            Self::parse_response(responses.first().unwrap_or(&Value::Null), "Email/set")?;
+
+        if let Some(err) = resp.not_created.as_ref().and_then(|m| m.get("draft")) {
+            return Err(set_error("Email/set", err, "Failed to save draft"));
+        }
+        if let Some(err) = resp
+            .not_destroyed
+            .as_ref()
+            .and_then(|m| destroy.first().and_then(|id| m.get(*id)))
+        {
+            return Err(set_error("Email/set", err, "Failed to remove old draft"));
+        }
+
+        resp.created
+            .and_then(|c| c.get("draft").cloned())
+            .and_then(|d| d.get("id").and_then(|v| v.as_str()).map(String::from))
+            .ok_or_else(|| Error::Jmap {
+                method: "Email/set".into(),
+                error_type: "unknown".into(),
+                description: "No draft ID returned".into(),
+            })
+    }
+
+    /// Submit an existing draft; on success it moves to Sent and loses its `$draft` keyword
+    #[instrument(skip(self))]
+    pub async fn send_draft(
+        &self,
+        email_id: &str,
+        send_at: Option<DateTime<Utc>>,
+    ) -> Result<String> {
+        let account_id = self.account_id()?;
+
+        let draft = self.get_email(email_id).await?;
+        if !draft.is_draft() {
+            return Err(Error::NotADraft(email_id.into()));
+        }
+
+        // Send as whichever identity the draft is from
+        let identities = self.list_identities().await?;
+        let identity =
+            Identity::select(&identities, draft.from.iter().flatten()).ok_or_else(|| {
+                Error::UnknownIdentity(
+                    draft
+                        .from
+                        .as_ref()
+                        .and_then(|f| f.first())
+                        .map(|a| a.email.clone())
+                        .unwrap_or_else(|| "(no From address)".into()),
+                )
+            })?;
+
+        let sent = self.find_mailbox("sent").await?;
+
+        let mut submission = json!({
+            "identityId": identity.id,
+            "emailId": email_id
+        });
+        if let Some(send_at) = send_at {
+            self.check_send_at(send_at)?;
+            let recipients: Vec<String> = [&draft.to, &draft.cc, &draft.bcc]
+                .into_iter()
+                .flatten()
+                .flatten()
+                .map(|a| a.email.clone())
+                .collect();
+            submission["envelope"] = envelope(&identity.email, &recipients, send_at);
+        }
+
+        let responses = self
+            .request(vec![json!([
+                "EmailSubmission/set",
+                {
+                    "accountId": account_id,
+                    "create": { "submission": submission },
+                    "onSuccessUpdateEmail": {
+                        "#submission": {
+                            "mailboxIds": { (sent.id): true },
+                            "keywords/$draft": null,
+                            "keywords/$seen": true
+                        }
+                    }
+                },
+                "s0"
+            ])])
+            .await?;
+
+        #[derive(Deserialize)]
+        struct SubmissionSetResponse {
+            created: Option<HashMap<String, Value>>,
+            #[serde(rename = "notCreated")]
+            not_created: Option<HashMap<String, Value>>,
+        }
+
+        let resp: SubmissionSetResponse = Self::parse_response(
+            responses.first().unwrap_or(&Value::Null),
+            "EmailSubmission/set",
+        )?;
+
+        if let Some(err) = resp.not_created.as_ref().and_then(|m| m.get("submission")) {
+            return Err(set_error(
+                "EmailSubmission/set",
+                err,
+                "Failed to send draft",
+            ));
+        }
+
+        debug!(email_id = %email_id, "Draft sent successfully");
+        resp.created
+            .and_then(|c| c.get("submission").cloned())
+            .and_then(|d| d.get("id").and_then(|v| v.as_str()).map(String::from))
+            .ok_or_else(|| Error::Jmap {
+                method: "EmailSubmission/set".into(),
+                error_type: "unknown".into(),
+                description: "No submission ID returned".into(),
+            })
+    }
+
+    /// Reject send times in the past or beyond the server's maxDelayedSend
+    fn check_send_at(&self, send_at: DateTime<Utc>) -> Result<()> {
+        let max = self.session()?.max_delayed_send(self.account_id()?);
+        if max == 0 {
+            return Err(Error::Config(
+                "Scheduled send is not supported for this account".into(),
+            ));
+        }
+        let delay = (send_at - Utc::now()).num_seconds();
+        if delay < 0 {
+            return Err(Error::Config(format!(
+                "Send time {} is in the past",
+                send_at
+            )));
+        }
+        if delay as u64 > max {
+            return Err(Error::Config(format!(
+                "Send time {} is further ahead than the server allows ({} days)",
+                send_at,
+                max / 86400
+            )));
+        }
+        Ok(())
+    }
+
+    /// List submissions that can still be canceled, with their emails
+    #[instrument(skip(self))]
+    pub async fn list_pending_submissions(&self) -> Result<(Vec<EmailSubmission>, Vec<Email>)> {
+        let account_id = self.account_id()?;
+
+        let responses = self
+            .request(vec![
+                json!([
+                    "EmailSubmission/query",
+                    {
+                        "accountId": account_id,
+                        "filter": { "undoStatus": "pending" },
+                        "sort": [{"property": "sentAt", "isAscending": true}]
+                    },
+                    "q0"
+                ]),
+                json!([
+                    "EmailSubmission/get",
+                    {
+                        "accountId": account_id,
+                        "#ids": {
+                            "resultOf": "q0",
+                            "name": "EmailSubmission/query",
+                            "path": "/ids"
+                        }
+                    },
+                    "g0"
+                ]),
+                json!([
+                    "Email/get",
+                    {
+                        "accountId": account_id,
+                        "#ids": {
+                            "resultOf": "g0",
+                            "name": "EmailSubmission/get",
+                            "path": "/list/*/emailId"
+                        },
+                        "properties": ["id", "threadId", "mailboxIds", "keywords", "from", "to", "cc", "subject", "preview"]
+                    },
+                    "e0"
+                ]),
+            ])
+            .await?;
+
+        #[derive(Deserialize)]
+        struct GetResponse<T> {
+            list: Vec<T>,
+        }
+
+        let submissions: GetResponse<EmailSubmission> = Self::parse_response(
+            responses.get(1).unwrap_or(&Value::Null),
+            "EmailSubmission/get",
+        )?;
+        let emails: GetResponse<Email> =
+            Self::parse_response(responses.get(2).unwrap_or(&Value::Null), "Email/get")?;
+
+        Ok((submissions.list, emails.list))
+    }
+
+    /// Cancel a pending submission; the email goes back to Drafts
+    #[instrument(skip(self))]
+    pub async fn cancel_submission(&self, submission_id: &str) -> Result<()> {
+        let account_id = self.account_id()?;
+
+        let drafts = self.find_mailbox("drafts").await?;
+
+        let responses = self
+            .request(vec![json!([
+                "EmailSubmission/set",
+                {
+                    "accountId": account_id,
+                    "update": {
+                        (submission_id): { "undoStatus": "canceled" }
+                    },
+                    "onSuccessUpdateEmail": {
+                        (submission_id): {
+                            "mailboxIds": { (drafts.id): true },
+                            "keywords/$draft": true
+                        }
+                    }
+                },
+                "c0"
+            ])])
+            .await?;
+
+        #[derive(Deserialize)]
+        struct SetResponse {
+            #[serde(rename = "notUpdated")]
+            not_updated: Option<HashMap<String, Value>>,
+        }
+
+        let resp: SetResponse = Self::parse_response(
+            responses.first().unwrap_or(&Value::Null),
+            "EmailSubmission/set",
+        )?;
+
+        if let Some(err) = resp.not_updated.as_ref().and_then(|m| m.get(submission_id)) {
+            return Err(set_error(
+                "EmailSubmission/set",
+                err,
+                "Failed to cancel submission",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Permanently destroy an email
+    #[instrument(skip(self))]
+    pub async fn destroy_email(&self, email_id: &str) -> Result<()> {
+        let result = self.destroy_emails(&[email_id.to_string()]).await?;
+        match result.failed.into_iter().next() {
+            Some(failure) => Err(Error::Jmap {
+                method: "Email/set".into(),
+                error_type: failure.error_type,
+                description: failure
+                    .description
+                    .unwrap_or_else(|| "Failed to delete email".into()),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Permanently destroy emails, in batches, reporting each failure
+    #[instrument(skip(self, ids), fields(count = ids.len()))]
+    pub async fn destroy_emails(&self, ids: &[String]) -> Result<BulkResult> {
+        let account_id = self.account_id()?;
+
+        #[derive(Deserialize)]
+        struct SetResponse {
+            destroyed: Option<Vec<String>>,
+            #[serde(rename = "notDestroyed")]
+            not_destroyed: Option<HashMap<String, Value>>,
+        }
+
+        let mut result = BulkResult::default();
+        for chunk in ids.chunks(self.limits()?.max_objects_in_set.max(1) as usize) {
+            let responses = self
+                .request(vec![json!([
+                    "Email/set",
+                    {
+                        "accountId": account_id,
+                        "destroy": chunk
+                    },
+                    "x0"
+                ])])
+                .await?;
+
+            let resp: SetResponse =
+                Self::parse_response(responses.first().unwrap_or(&Value::Null), "Email/set")?;
+            result.record(
+                chunk,
+                resp.destroyed.iter().flatten(),
+                resp.not_destroyed.as_ref(),
+            );
+            debug!(
+                done = result.succeeded.len(),
+                failed = result.failed.len(),
+                "Bulk destroy batch"
+            );
+        }
+        Ok(result)
+    }
+
     #[instrument(skip(self))]
     pub async fn move_email(&self, email_id: &str, mailbox_id: &str) -> Result<()> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        let account_id = self.account_id()?;
 
         let responses = self
             .request(vec![json!([
@@ -710,7 +1858,50 @@ impl JmapClient {
             });
         }
 
-        Ok(())
+        Ok(())
+    }
+
+    /// Apply the same Email/set patch to many emails, in batches, reporting each failure
+    #[instrument(skip(self, ids, patch), fields(count = ids.len()))]
+    pub async fn update_emails(&self, ids: &[String], patch: Value) -> Result<BulkResult> {
+        let account_id = self.account_id()?;
+
+        #[derive(Deserialize)]
+        struct SetResponse {
+            updated: Option<HashMap<String, Value>>,
+            #[serde(rename = "notUpdated")]
+            not_updated: Option<HashMap<String, Value>>,
+        }
+
+        let mut result = BulkResult::default();
+        for chunk in ids.chunks(self.limits()?.max_objects_in_set.max(1) as usize) {
+            let update: HashMap<&str, &Value> =
+                chunk.iter().map(|id| (id.as_str(), &patch)).collect();
+            let responses = self
+                .request(vec![json!([
+                    "Email/set",
+                    {
+                        "accountId": account_id,
+                        "update": update
+                    },
+                    "b0"
+                ])])
+                .await?;
+
+            let resp: SetResponse =
+                Self::parse_response(responses.first().unwrap_or(&Value::Null), "Email/set")?;
+            result.record(
+                chunk,
+                resp.updated.iter().flat_map(|m| m.keys()),
+                resp.not_updated.as_ref(),
+            );
+            debug!(
+                done = result.succeeded.len(),
+                failed = result.failed.len(),
+                "Bulk update batch"
+            );
+        }
+        Ok(result)
     }
 
     #[instrument(skip(self))]
@@ -723,9 +1914,7 @@ impl JmapClient {
     #[instrument(skip(self))]
     pub async fn download_blob(&self, blob_id: &str) -> Result<Vec<u8>> {
         let session = self.session()?;
-        let account_id = session
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        let account_id = self.account_id()?;
 
         // downloadUrl template: https://api.fastmail.com/jmap/download/{accountId}/{blobId}/{name}?accept={type}
         let url = session
@@ -737,14 +1926,14 @@ impl JmapClient {
 
         debug!(url = %url, "Downloading blob");
         let resp = self
-            .client
-            .get(&url)
-            .bearer_auth(&self.token)
-            .send()
+            .send(true, || self.client.get(&url).bearer_auth(&self.token))
             .await?;
 
         match resp.status().as_u16() {
-            401 => return Err(Error::InvalidToken("Token expired or invalid".into())),
+            401 => {
+                self.invalidate_cache();
+                return Err(Error::InvalidToken("Token expired or invalid".into()));
+            }
             404 => return Err(Error::Config(format!("Blob not found: {}", blob_id))),
             429 => return Err(Error::RateLimited),
             500..=599 => return Err(Error::Server(format!("Server error: {}", resp.status()))),
@@ -755,8 +1944,104 @@ impl JmapClient {
         Ok(bytes.to_vec())
     }
 
+    /// Upload raw bytes to the account's blob store, returning the blob ID. `name` identifies
+    /// the upload in errors.
+    #[instrument(skip(self, data))]
+    pub async fn upload_blob(
+        &self,
+        name: &str,
+        data: Vec<u8>,
+        content_type: &str,
+    ) -> Result<String> {
+        let session = self.session()?;
+        let account_id = self.account_id()?;
+
+        // uploadUrl template: https://api.fastmail.com/jmap/upload/{accountId}/
+        let url = session.upload_url.replace("{accountId}", account_id);
+
+        debug!(url = %url, size = data.len(), "Uploading blob");
+        let resp = self
+            .client
+            .post(&url)
+            .bearer_auth(&self.token)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(data)
+            .send()
+            .await?;
+
+        match resp.status().as_u16() {
+            401 => {
+                self.invalidate_cache();
+                return Err(Error::InvalidToken("Token expired or invalid".into()));
+            }
+            429 => return Err(Error::RateLimited),
+            // e.g. 413 when the file is over a limit the session didn't advertise
+            _ if !resp.status().is_success() => {
+                return Err(Error::Upload {
+                    name: name.to_string(),
+                    reason: format!("server returned {}", resp.status()),
+                });
+            }
+            _ => {}
+        }
+
+        #[derive(Deserialize)]
+        struct UploadResponse {
+            #[serde(rename = "blobId")]
+            blob_id: String,
+        }
+
+        let upload: UploadResponse = resp.json().await?;
+        Ok(upload.blob_id)
+    }
+
+    /// Upload local files and describe them as attachment body parts
+    #[instrument(skip(self))]
+    pub async fn upload_attachments(&self, paths: &[String]) -> Result<Vec<EmailBodyPart>> {
+        let max_size = self.limits()?.max_size_upload;
+        let mut parts = Vec::with_capacity(paths.len());
+
+        for path in paths {
+            let path = Path::new(path);
+            let name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .ok_or_else(|| {
+                    Error::Config(format!("Invalid attachment path: {}", path.display()))
+                })?
+                .to_string();
+            // Check the size first, so a huge file isn't read only to be rejected
+            let size = tokio::fs::metadata(path).await?.len();
+            if size > max_size {
+                return Err(Error::Upload {
+                    name,
+                    reason: format!(
+                        "{} bytes exceeds the server's maxSizeUpload ({} bytes)",
+                        size, max_size
+                    ),
+                });
+            }
+            let data = tokio::fs::read(path).await?;
+            let content_type = infer_mime(&name);
+            let blob_id = self.upload_blob(&name, data, &content_type).await?;
+
+            parts.push(EmailBodyPart {
+                part_id: None,
+                blob_id: Some(blob_id),
+                size,
+                name: Some(name),
+                content_type: Some(content_type),
+                charset: None,
+                disposition: Some("attachment".into()),
+                cid: None,
+            });
+        }
+
+        Ok(parts)
+    }
+
     /// Send a reply to an existing email with proper threading headers
-    #[instrument(skip(self, body))]
+    #[instrument(skip(self, body, options))]
     pub async fn reply_email(
         &self,
         original: &Email,
@@ -764,14 +2049,18 @@ impl JmapClient {
         reply_all: bool,
         cc: Vec<EmailAddress>,
         bcc: Vec<EmailAddress>,
+        options: &ComposeOptions,
     ) -> Result<String> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        let account_id = self.account_id()?;
 
-        let identities = self.list_identities().await?;
-        let identity = identities.first().ok_or(Error::IdentityNotFound)?;
+        let identity = match options.identity {
+            Some(ref identity) => identity.clone(),
+            None => {
+                self.reply_identity(original, options.from.as_deref())
+                    .await?
+            }
+        };
+        let (body, html) = compose_body(&identity, body, options);
         let my_email = identity.email.to_lowercase();
 
         let sent = self.find_mailbox("sent").await?;
@@ -831,45 +2120,18 @@ impl JmapClient {
             "from".into(),
             json!([{ "email": identity.email, "name": identity.name }]),
         );
-        email_create.insert(
-            "to".into(),
-            json!(
-                to_addrs
-                    .iter()
-                    .map(|a| json!({"email": a.email, "name": a.name}))
-                    .collect::<Vec<_>>()
-            ),
-        );
+        email_create.insert("to".into(), address_list(&to_addrs));
         if !cc_addrs.is_empty() {
-            email_create.insert(
-                "cc".into(),
-                json!(
-                    cc_addrs
-                        .iter()
-                        .map(|a| json!({"email": a.email, "name": a.name}))
-                        .collect::<Vec<_>>()
-                ),
-            );
+            email_create.insert("cc".into(), address_list(&cc_addrs));
         }
         if !bcc.is_empty() {
-            email_create.insert(
-                "bcc".into(),
-                json!(
-                    bcc.iter()
-                        .map(|a| json!({"email": a.email, "name": a.name}))
-                        .collect::<Vec<_>>()
-                ),
-            );
+            email_create.insert("bcc".into(), address_list(&bcc));
         }
         email_create.insert("subject".into(), json!(subject));
-        email_create.insert(
-            "bodyValues".into(),
-            json!({ "body": { "value": body, "charset": "utf-8" } }),
-        );
-        email_create.insert(
-            "textBody".into(),
-            json!([{ "partId": "body", "type": "text/plain" }]),
-        );
+        insert_body(&mut email_create, &body, html.as_deref());
+        if !options.attachments.is_empty() {
+            email_create.insert("attachments".into(), attachment_list(&options.attachments));
+        }
 
         // Threading headers
         if let Some(ref msg_id) = original.message_id {
@@ -879,85 +2141,20 @@ impl JmapClient {
             email_create.insert("references".into(), json!(references));
         }
 
-        let responses = self
-            .request(vec![
-                json!([
-                    "Email/set",
-                    {
-                        "accountId": account_id,
-                        "create": { "email": email_create }
-                    },
-                    "e0"
-                ]),
-                json!([
-                    "EmailSubmission/set",
-                    {
-                        "accountId": account_id,
-                        "create": {
-                            "submission": {
-                                "identityId": identity.id,
-                                "emailId": "#email"
-                            }
-                        },
-                        "onSuccessUpdateEmail": {
-                            "#submission": {
-                                "keywords/$seen": true
-                            }
-                        }
-                    },
-                    "s0"
-                ]),
-            ])
+        let email_id = self
+            .create_and_submit(account_id, &identity, email_create, options.send_at)
             .await?;
 
-        #[derive(Deserialize)]
-        struct EmailSetResponse {
-            created: Option<HashMap<String, Value>>,
-            #[serde(rename = "notCreated")]
-            not_created: Option<HashMap<String, Value>>,
-        }
-
-        let email_resp: EmailSetResponse =
-            Self::parse_response(responses.first().unwrap_or(&Value::Null), "Email/set")?;
-
-        if let Some(ref not_created) = email_resp.not_created
-            && let Some(err) = not_created.get("email")
-        {
-            let error_type = err
-                .get("type")
-                .and_then(|v: &Value| v.as_str())
-                .unwrap_or("unknown");
-            let description = err
-                .get("description")
-                .and_then(|v: &Value| v.as_str())
-                .unwrap_or("Failed to create email");
-            return Err(Error::Jmap {
-                method: "Email/set".into(),
-                error_type: error_type.into(),
-                description: description.into(),
-            });
-        }
-
-        let email_id = email_resp
-            .created
-            .and_then(|c: HashMap<String, Value>| c.get("email").cloned())
-            .and_then(|d: Value| {
-                d.get("id")
-                    .and_then(|v: &Value| v.as_str())
-                    .map(String::from)
-            })
-            .ok_or_else(|| Error::Jmap {
-                method: "Email/set".into(),
-                error_type: "unknown".into(),
-                description: "No email ID returned".into(),
-            })?;
-
         debug!(email_id = %email_id, "Reply sent successfully");
         Ok(email_id)
     }
 
     /// Forward an email with proper attribution
-    #[instrument(skip(self, body))]
+    ///
+    /// Inline mode quotes the original body and re-attaches its attachments by blob ID;
+    /// attachment mode sends the original message itself as a `message/rfc822` part.
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(skip(self, body, options))]
     pub async fn forward_email(
         &self,
         original: &Email,
@@ -965,14 +2162,14 @@ impl JmapClient {
         body: &str,
         cc: Vec<EmailAddress>,
         bcc: Vec<EmailAddress>,
+        mode: ForwardMode,
+        options: &ComposeOptions,
     ) -> Result<String> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        let account_id = self.account_id()?;
 
-        let identities = self.list_identities().await?;
-        let identity = identities.first().ok_or(Error::IdentityNotFound)?;
+        let identity = self.compose_identity(options).await?;
+        // The signature belongs to the new message, above the forwarded block
+        let (body, html) = compose_body(&identity, body, options);
 
         let sent = self.find_mailbox("sent").await?;
 
@@ -1010,14 +2207,69 @@ impl JmapClient {
 
         let date = original.received_at.as_deref().unwrap_or("unknown date");
 
-        let full_body = format!(
-            "{}\n\n---------- Forwarded message ---------\nFrom: {}\nDate: {}\nSubject: {}\n\n{}",
-            body,
-            sender,
-            date,
-            original.subject.as_deref().unwrap_or(""),
-            original_body
-        );
+        let mut attachments: Vec<EmailBodyPart> = Vec::new();
+        let full_body = match mode {
+            ForwardMode::Inline => {
+                // Carry the original attachments over without re-uploading them
+                attachments.extend(
+                    original
+                        .attachments
+                        .iter()
+                        .flatten()
+                        .filter(|a| a.blob_id.is_some())
+                        .cloned(),
+                );
+                format!(
+                    "{}\n\n---------- Forwarded message ---------\nFrom: {}\nDate: {}\nSubject: {}\n\n{}",
+                    body,
+                    sender,
+                    date,
+                    original.subject.as_deref().unwrap_or(""),
+                    original_body
+                )
+            }
+            ForwardMode::Attachment => {
+                let blob_id = original.blob_id.clone().ok_or_else(|| {
+                    Error::Config(format!("Email {} has no blob ID to attach", original.id))
+                })?;
+                attachments.push(EmailBodyPart {
+                    part_id: None,
+                    blob_id: Some(blob_id),
+                    size: original.size,
+                    name: Some(safe_file_name(
+                        original.subject.as_deref().unwrap_or(""),
+                        "eml",
+                        "forwarded message",
+                    )),
+                    content_type: Some("message/rfc822".into()),
+                    charset: None,
+                    disposition: Some("attachment".into()),
+                    cid: None,
+                });
+                body.clone()
+            }
+        };
+        attachments.extend(options.attachments.iter().cloned());
+
+        // Mirror the forwarded block into the HTML alternative when composing HTML
+        let full_html = html.as_ref().map(|html| match mode {
+            ForwardMode::Inline => {
+                let original_html = original
+                    .html_content()
+                    .map(String::from)
+                    .unwrap_or_else(|| format!("<pre>{}</pre>", escape_html(original_body)));
+                format!(
+                    "{}<br><br><div>---------- Forwarded message ---------<br>\
+                    From: {}<br>Date: {}<br>Subject: {}<br><br>{}</div>",
+                    html,
+                    escape_html(&sender),
+                    escape_html(date),
+                    escape_html(original.subject.as_deref().unwrap_or("")),
+                    original_html
+                )
+            }
+            ForwardMode::Attachment => html.clone(),
+        });
 
         let mut email_create: HashMap<String, Value> = HashMap::new();
         // Create directly in Sent - no draft needed
@@ -1026,116 +2278,22 @@ impl JmapClient {
             "from".into(),
             json!([{ "email": identity.email, "name": identity.name }]),
         );
-        email_create.insert(
-            "to".into(),
-            json!(
-                to.iter()
-                    .map(|a| json!({"email": a.email, "name": a.name}))
-                    .collect::<Vec<_>>()
-            ),
-        );
+        email_create.insert("to".into(), address_list(&to));
         if !cc.is_empty() {
-            email_create.insert(
-                "cc".into(),
-                json!(
-                    cc.iter()
-                        .map(|a| json!({"email": a.email, "name": a.name}))
-                        .collect::<Vec<_>>()
-                ),
-            );
+            email_create.insert("cc".into(), address_list(&cc));
         }
         if !bcc.is_empty() {
-            email_create.insert(
-                "bcc".into(),
-                json!(
-                    bcc.iter()
-                        .map(|a| json!({"email": a.email, "name": a.name}))
-                        .collect::<Vec<_>>()
-                ),
-            );
+            email_create.insert("bcc".into(), address_list(&bcc));
         }
         email_create.insert("subject".into(), json!(subject));
-        email_create.insert(
-            "bodyValues".into(),
-            json!({ "body": { "value": full_body, "charset": "utf-8" } }),
-        );
-        email_create.insert(
-            "textBody".into(),
-            json!([{ "partId": "body", "type": "text/plain" }]),
-        );
-
-        let responses = self
-            .request(vec![
-                json!([
-                    "Email/set",
-                    {
-                        "accountId": account_id,
-                        "create": { "email": email_create }
-                    },
-                    "e0"
-                ]),
-                json!([
-                    "EmailSubmission/set",
-                    {
-                        "accountId": account_id,
-                        "create": {
-                            "submission": {
-                                "identityId": identity.id,
-                                "emailId": "#email"
-                            }
-                        },
-                        "onSuccessUpdateEmail": {
-                            "#submission": {
-                                "keywords/$seen": true
-                            }
-                        }
-                    },
-                    "s0"
-                ]),
-            ])
-            .await?;
-
-        #[derive(Deserialize)]
-        struct EmailSetResponse {
-            created: Option<HashMap<String, Value>>,
-            #[serde(rename = "notCreated")]
-            not_created: Option<HashMap<String, Value>>,
-        }
-
-        let email_resp: EmailSetResponse =
-            Self::parse_response(responses.first().unwrap_or(&Value::Null), "Email/set")?;
-
-        if let Some(ref not_created) = email_resp.not_created
-            && let Some(err) = not_created.get("email")
-        {
-            let error_type = err
-                .get("type")
-                .and_then(|v: &Value| v.as_str())
-                .unwrap_or("unknown");
-            let description = err
-                .get("description")
-                .and_then(|v: &Value| v.as_str())
-                .unwrap_or("Failed to create email");
-            return Err(Error::Jmap {
-                method: "Email/set".into(),
-                error_type: error_type.into(),
-                description: description.into(),
-            });
+        insert_body(&mut email_create, &full_body, full_html.as_deref());
+        if !attachments.is_empty() {
+            email_create.insert("attachments".into(), attachment_list(&attachments));
         }
 
-        let email_id = email_resp
-            .created
-            .and_then(|c: HashMap<String, Value>| c.get("email").cloned())
-            .and_then(|d: Value| {
-                d.get("id")
-                    .and_then(|v: &Value| v.as_str())
-                    .map(String::from)
-            })
-            .ok_or_else(|| Error::Jmap {
-                method: "Email/set".into(),
-                error_type: "unknown".into(),
-                description: "No email ID returned".into(),
-            })?;
+        let email_id = self
+            .create_and_submit(account_id, &identity, email_create, options.send_at)
+            .await?;
 
         debug!(email_id = %email_id, "Forward sent successfully");
         Ok(email_id)
@@ -1148,10 +2306,7 @@ impl JmapClient {
         email_id: &str,
         keywords: HashMap<String, bool>,
     ) -> Result<()> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        let account_id = self.account_id()?;
 
         let responses = self
             .request(vec![json!([
@@ -1201,10 +2356,8 @@ impl JmapClient {
     /// List all masked email addresses
     #[instrument(skip(self))]
     pub async fn list_masked_emails(&self) -> Result<Vec<MaskedEmail>> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        self.require_capability(MASKED_EMAIL_CAPABILITY)?;
+        let account_id = self.account_id()?;
 
         let responses = self
             .request(vec![json!([
@@ -1236,10 +2389,8 @@ impl JmapClient {
         description: Option<&str>,
         email_prefix: Option<&str>,
     ) -> Result<MaskedEmail> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        self.require_capability(MASKED_EMAIL_CAPABILITY)?;
+        let account_id = self.account_id()?;
 
         let mut create_obj: HashMap<String, Value> = HashMap::new();
         create_obj.insert("state".into(), json!("enabled"));
@@ -1311,10 +2462,8 @@ impl JmapClient {
         for_domain: Option<&str>,
         description: Option<&str>,
     ) -> Result<()> {
-        let account_id = self
-            .session()?
-            .primary_account_id()
-            .ok_or_else(|| Error::Config("No primary account".into()))?;
+        self.require_capability(MASKED_EMAIL_CAPABILITY)?;
+        let account_id = self.account_id()?;
 
         let mut update_obj: HashMap<String, Value> = HashMap::new();
         if let Some(s) = state {
@@ -1368,3 +2517,128 @@ impl JmapClient {
         Ok(())
     }
 }
+
+/// Convert addresses to the JMAP EmailAddress JSON shape
+fn address_list(addrs: &[EmailAddress]) -> Value {
+    json!(
+        addrs
+            .iter()
+            .map(|a| json!({"email": a.email, "name": a.name}))
+            .collect::<Vec<_>>()
+    )
+}
+
+/// Convert blobs to EmailBodyPart JSON for Email/set. Parts carried over from another
+/// email keep their disposition and Content-ID, so inline images still resolve.
+fn attachment_list(parts: &[EmailBodyPart]) -> Value {
+    json!(
+        parts
+            .iter()
+            .map(|p| {
+                let mut part = json!({
+                    "blobId": p.blob_id,
+                    "type": p.content_type.as_deref().unwrap_or("application/octet-stream"),
+                    "name": p.name,
+                    "disposition": p.disposition.as_deref().unwrap_or("attachment"),
+                });
+                if let Some(ref cid) = p.cid {
+                    part["cid"] = json!(cid);
+                }
+                part
+            })
+            .collect::<Vec<_>>()
+    )
+}
+
+/// SMTP envelope that holds the message on the server until `send_at` (RFC 4865 FUTURERELEASE).
+/// Setting an envelope replaces the server's default, so every recipient must be listed.
+fn envelope(from: &str, recipients: &[String], send_at: DateTime<Utc>) -> Value {
+    json!({
+        "mailFrom": {
+            "email": from,
+            "parameters": {
+                "HOLDUNTIL": send_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            }
+        },
+        "rcptTo": recipients
+            .iter()
+            .map(|email| json!({ "email": email }))
+            .collect::<Vec<_>>()
+    })
+}
+
+/// Convert a SetError from a notCreated/notUpdated/notDestroyed map into an Error
+fn set_error(method: &str, err: &Value, fallback: &str) -> Error {
+    Error::Jmap {
+        method: method.into(),
+        error_type: err
+            .get("type")
+            .and_then(|v| v.as_str())
+            .unwrap_or("unknown")
+            .into(),
+        description: err
+            .get("description")
+            .and_then(|v| v.as_str())
+            .unwrap_or(fallback)
+            .into(),
+    }
+}
+
+/// Text and HTML bodies for a new message, with the identity's signature appended if requested
+fn compose_body(
+    identity: &Identity,
+    body: &str,
+    options: &ComposeOptions,
+) -> (String, Option<String>) {
+    let html = options.html.clone();
+    if !options.signature {
+        return (body.to_string(), html);
+    }
+    let text_sig = identity.text_signature.as_deref().filter(|s| !s.is_empty());
+    let html_sig = identity.html_signature.as_deref().filter(|s| !s.is_empty());
+
+    let text = match text_sig {
+        Some(sig) => format!("{}\n\n-- \n{}", body, sig),
+        None => body.to_string(),
+    };
+    let html = html.map(|html| match (html_sig, text_sig) {
+        (Some(sig), _) => format!("{}<br><br>{}", html, sig),
+        (None, Some(sig)) => format!(
+            "{}<br><br>-- <br>{}",
+            html,
+            escape_html(sig).replace('\n', "<br>")
+        ),
+        (None, None) => html,
+    });
+    (text, html)
+}
+
+/// Insert the text body, plus an HTML alternative if given, into an Email/set create object.
+/// With both parts present the server builds a multipart/alternative message.
+fn insert_body(email_create: &mut HashMap<String, Value>, text: &str, html: Option<&str>) {
+    match html {
+        Some(html) => {
+            email_create.insert(
+                "bodyValues".into(),
+                json!({
+                    "body": { "value": text, "charset": "utf-8" },
+                    "html": { "value": html, "charset": "utf-8" }
+                }),
+            );
+            email_create.insert(
+                "htmlBody".into(),
+                json!([{ "partId": "html", "type": "text/html" }]),
+            );
+        }
+        None => {
+            email_create.insert(
+                "bodyValues".into(),
+                json!({ "body": { "value": text, "charset": "utf-8" } }),
+            );
+        }
+    }
+    email_create.insert(
+        "textBody".into(),
+        json!([{ "partId": "body", "type": "text/plain" }]),
+    );
+}
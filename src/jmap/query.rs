@@ -0,0 +1,424 @@
+//! Gmail-style search expressions compiled to JMAP Email/query filters
+//!
+//! `from:alice OR from:bob -subject:newsletter has:attachment in:Archive`
+//!
+//! Terms next to each other are ANDed, `OR` binds tighter than the implicit AND
+//! (as in Gmail), `-term` or `NOT term` negates, and parentheses group.
+
+use crate::error::{Error, Result};
+use crate::util::parse_size;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+/// Parsed search expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    Term(Term),
+}
+
+/// A single search condition
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// Header or body match: text, from, to, cc, bcc, subject, body
+    Match(&'static str, String),
+    HasAttachment,
+    /// `is:` keywords; `present` is false for negated keywords like `is:unread`
+    Keyword {
+        keyword: String,
+        present: bool,
+    },
+    Before(String),
+    After(String),
+    MinSize(usize),
+    MaxSize(usize),
+    InMailbox(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    /// Quoted string, never treated as an operator or field
+    Quoted(String),
+    Open,
+    Close,
+    Minus,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '-' => {
+                chars.next();
+                tokens.push(Token::Minus);
+            }
+            '"' => {
+                chars.next();
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => s.push(c),
+                        None => return Err(Error::InvalidQuery("unterminated quote".into())),
+                    }
+                }
+                tokens.push(Token::Quoted(s));
+            }
+            _ => {
+                let mut s = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    // field:"quoted value"
+                    if c == '"' {
+                        loop {
+                            match chars.next() {
+                                Some('"') => break,
+                                Some(c) => s.push(c),
+                                None => {
+                                    return Err(Error::InvalidQuery("unterminated quote".into()));
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    s.push(c);
+                }
+                tokens.push(Token::Word(s));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == keyword)
+    }
+
+    fn parse_and(&mut self) -> Result<Query> {
+        let mut terms = Vec::new();
+        while let Some(token) = self.peek() {
+            if *token == Token::Close {
+                break;
+            }
+            if self.peek_keyword("AND") {
+                self.next();
+                continue;
+            }
+            terms.push(self.parse_or()?);
+        }
+        match terms.len() {
+            0 => Err(Error::InvalidQuery("empty expression".into())),
+            1 => Ok(terms.remove(0)),
+            _ => Ok(Query::And(terms)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Query> {
+        let mut terms = vec![self.parse_unary()?];
+        while self.peek_keyword("OR") {
+            self.next();
+            terms.push(self.parse_unary()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Query::Or(terms)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Query> {
+        match self.next() {
+            Some(Token::Minus) => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Word(w)) if w == "NOT" => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Open) => {
+                let inner = self.parse_and()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(inner),
+                    _ => Err(Error::InvalidQuery("missing closing parenthesis".into())),
+                }
+            }
+            Some(Token::Word(w)) if w == "OR" || w == "AND" => Err(Error::InvalidQuery(format!(
+                "{} needs a term on both sides",
+                w
+            ))),
+            Some(Token::Word(w)) => parse_term(&w).map(Query::Term),
+            Some(Token::Quoted(s)) => Ok(Query::Term(Term::Match("text", s))),
+            Some(Token::Close) => Err(Error::InvalidQuery("unexpected ')'".into())),
+            None => Err(Error::InvalidQuery("expression ends unexpectedly".into())),
+        }
+    }
+}
+
+fn parse_term(word: &str) -> Result<Term> {
+    let Some((field, value)) = word.split_once(':') else {
+        return Ok(Term::Match("text", word.to_string()));
+    };
+    if value.is_empty() {
+        return Err(Error::InvalidQuery(format!("{}: needs a value", field)));
+    }
+
+    let term = match field.to_lowercase().as_str() {
+        "from" => Term::Match("from", value.into()),
+        "to" => Term::Match("to", value.into()),
+        "cc" => Term::Match("cc", value.into()),
+        "bcc" => Term::Match("bcc", value.into()),
+        "subject" => Term::Match("subject", value.into()),
+        "body" => Term::Match("body", value.into()),
+        "text" => Term::Match("text", value.into()),
+        "in" | "mailbox" => Term::InMailbox(value.into()),
+        "before" => Term::Before(normalize_date(value)),
+        "after" => Term::After(normalize_date(value)),
+        "larger" | "size" => Term::MinSize(
+            parse_size(value).ok_or_else(|| Error::InvalidQuery(format!("bad size: {}", value)))?,
+        ),
+        "smaller" => Term::MaxSize(
+            parse_size(value).ok_or_else(|| Error::InvalidQuery(format!("bad size: {}", value)))?,
+        ),
+        "has" => match value.to_lowercase().as_str() {
+            "attachment" | "attachments" => Term::HasAttachment,
+            _ => return Err(Error::InvalidQuery(format!("unknown has:{}", value))),
+        },
+        "is" => {
+            let (keyword, present) = match value.to_lowercase().as_str() {
+                "unread" => ("$seen", false),
+                "read" | "seen" => ("$seen", true),
+                "flagged" | "starred" => ("$flagged", true),
+                "unflagged" | "unstarred" => ("$flagged", false),
+                "draft" => ("$draft", true),
+                "answered" | "replied" => ("$answered", true),
+                _ => return Err(Error::InvalidQuery(format!("unknown is:{}", value))),
+            };
+            Term::Keyword {
+                keyword: keyword.into(),
+                present,
+            }
+        }
+        "keyword" | "label" => Term::Keyword {
+            keyword: value.into(),
+            present: true,
+        },
+        // Not a field we know ("re:meeting", a URL) - search it as text
+        _ => Term::Match("text", word.to_string()),
+    };
+    Ok(term)
+}
+
+/// Expand bare dates to the UTCDate form JMAP expects
+pub fn normalize_date(date: &str) -> String {
+    if date.contains('T') {
+        date.to_string()
+    } else {
+        format!("{}T00:00:00Z", date)
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            pos: 0,
+        };
+        let query = parser.parse_and()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(Error::InvalidQuery("unexpected ')'".into()));
+        }
+        Ok(query)
+    }
+
+    /// Mailbox names referenced by `in:` terms, to be resolved to IDs before compiling
+    pub fn mailboxes(&self) -> Vec<&str> {
+        match self {
+            Query::And(qs) | Query::Or(qs) => qs.iter().flat_map(|q| q.mailboxes()).collect(),
+            Query::Not(q) => q.mailboxes(),
+            Query::Term(Term::InMailbox(name)) => vec![name.as_str()],
+            Query::Term(_) => vec![],
+        }
+    }
+
+    /// Compile to a JMAP FilterOperator/FilterCondition, with mailbox names mapped to IDs
+    pub fn to_filter(&self, mailbox_ids: &HashMap<String, String>) -> Result<Value> {
+        let operator = |op: &str, qs: &[Query]| -> Result<Value> {
+            let conditions = qs
+                .iter()
+                .map(|q| q.to_filter(mailbox_ids))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(json!({ "operator": op, "conditions": conditions }))
+        };
+
+        match self {
+            Query::And(qs) => operator("AND", qs),
+            Query::Or(qs) => operator("OR", qs),
+            Query::Not(q) => operator("NOT", std::slice::from_ref(q)),
+            Query::Term(term) => Ok(match term {
+                Term::Match(field, value) => json!({ *field: value }),
+                Term::HasAttachment => json!({ "hasAttachment": true }),
+                Term::Keyword {
+                    keyword,
+                    present: true,
+                } => json!({ "hasKeyword": keyword }),
+                Term::Keyword { keyword, .. } => json!({ "notKeyword": keyword }),
+                Term::Before(date) => json!({ "before": date }),
+                Term::After(date) => json!({ "after": date }),
+                Term::MinSize(size) => json!({ "minSize": size }),
+                Term::MaxSize(size) => json!({ "maxSize": size }),
+                Term::InMailbox(name) => {
+                    let id = mailbox_ids
+                        .get(name)
+                        .ok_or_else(|| Error::MailboxNotFound(name.clone()))?;
+                    json!({ "inMailbox": id })
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(field: &'static str, value: &str) -> Query {
+        Query::Term(Term::Match(field, value.to_string()))
+    }
+
+    #[test]
+    fn test_parse_implicit_and() {
+        let q = Query::parse("from:alice invoice").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![text("from", "alice"), text("text", "invoice")])
+        );
+    }
+
+    #[test]
+    fn test_parse_or_binds_tighter_than_and() {
+        let q = Query::parse("from:alice OR from:bob has:attachment").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![
+                Query::Or(vec![text("from", "alice"), text("from", "bob")]),
+                Query::Term(Term::HasAttachment),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_negation_and_groups() {
+        let q = Query::parse("-subject:newsletter NOT (from:a OR from:b)").unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![
+                Query::Not(Box::new(text("subject", "newsletter"))),
+                Query::Not(Box::new(Query::Or(vec![
+                    text("from", "a"),
+                    text("from", "b")
+                ]))),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_quoted_values() {
+        let q = Query::parse(r#"subject:"weekly report" "exact phrase""#).unwrap();
+        assert_eq!(
+            q,
+            Query::And(vec![
+                text("subject", "weekly report"),
+                text("text", "exact phrase")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_field_is_text() {
+        assert_eq!(
+            Query::parse("re:meeting").unwrap(),
+            text("text", "re:meeting")
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Query::parse("").is_err());
+        assert!(Query::parse("(from:a").is_err());
+        assert!(Query::parse("from:a)").is_err());
+        assert!(Query::parse("from:a OR").is_err());
+        assert!(Query::parse("is:bogus").is_err());
+        assert!(Query::parse("subject:\"open").is_err());
+    }
+
+    #[test]
+    fn test_compile_filter() {
+        let q = Query::parse(
+            "from:alice OR from:bob -subject:newsletter after:2024-01-01 in:Archive is:unread",
+        )
+        .unwrap();
+        assert_eq!(q.mailboxes(), vec!["Archive"]);
+
+        let ids = HashMap::from([("Archive".to_string(), "mb1".to_string())]);
+        let filter = q.to_filter(&ids).unwrap();
+        assert_eq!(
+            filter,
+            json!({
+                "operator": "AND",
+                "conditions": [
+                    {
+                        "operator": "OR",
+                        "conditions": [{ "from": "alice" }, { "from": "bob" }]
+                    },
+                    {
+                        "operator": "NOT",
+                        "conditions": [{ "subject": "newsletter" }]
+                    },
+                    { "after": "2024-01-01T00:00:00Z" },
+                    { "inMailbox": "mb1" },
+                    { "notKeyword": "$seen" }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_compile_unknown_mailbox() {
+        let q = Query::parse("in:Nowhere").unwrap();
+        assert!(matches!(
+            q.to_filter(&HashMap::new()),
+            Err(Error::MailboxNotFound(_))
+        ));
+    }
+}
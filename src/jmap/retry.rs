@@ -0,0 +1,162 @@
+//! Retry policy for rate limits and transient server errors.
+//!
+//! Only idempotent requests are retried: fetching the session, downloads, and
+//! JMAP requests made up entirely of `*/get`, `*/query`, `*/changes` and
+//! `*/queryChanges` calls. Anything that sets state (notably
+//! `EmailSubmission/set`) fails on the first error so a message is never sent twice.
+
+use chrono::{DateTime, Utc};
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::time::Duration;
+
+const BASE_DELAY: Duration = Duration::from_millis(500);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// Longest `Retry-After` we'll wait; anything longer fails instead
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying
+    pub max_retries: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_retries: 3 }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before retry number `attempt` (0-based), or None to give up.
+    /// A server-supplied `Retry-After` wins over the exponential backoff.
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if attempt >= self.max_retries {
+            return None;
+        }
+        match retry_after {
+            Some(wait) if wait > MAX_RETRY_AFTER => None,
+            Some(wait) => Some(wait),
+            None => {
+                let backoff = BASE_DELAY
+                    .saturating_mul(2u32.saturating_pow(attempt))
+                    .min(MAX_DELAY);
+                Some(backoff + jitter(backoff / 2))
+            }
+        }
+    }
+}
+
+/// A pseudo-random duration in `0..=max`, so parallel scripts don't retry in lockstep
+fn jitter(max: Duration) -> Duration {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    max.mul_f64(f64::from(nanos % 1000) / 1000.0)
+}
+
+/// 429 and 5xx are worth retrying; everything else is final
+pub fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Parse a `Retry-After` header: delay in seconds, or an HTTP date
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// True when every call in a JMAP request only reads
+pub fn is_idempotent(method_calls: &[Value]) -> bool {
+    method_calls.iter().all(|call| {
+        call.get(0).and_then(|m| m.as_str()).is_some_and(|method| {
+            ["/get", "/query", "/changes", "/queryChanges"]
+                .iter()
+                .any(|suffix| method.ends_with(suffix))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_delay_backoff_grows_and_stops() {
+        let policy = RetryPolicy { max_retries: 3 };
+        let first = policy.delay(0, None).unwrap();
+        assert!(first >= BASE_DELAY && first <= BASE_DELAY * 3 / 2);
+        let third = policy.delay(2, None).unwrap();
+        assert!(third >= BASE_DELAY * 4);
+        assert!(policy.delay(3, None).is_none());
+        assert!(RetryPolicy { max_retries: 0 }.delay(0, None).is_none());
+    }
+
+    #[test]
+    fn test_delay_capped() {
+        let policy = RetryPolicy { max_retries: 100 };
+        assert!(policy.delay(60, None).unwrap() <= MAX_DELAY * 3 / 2);
+    }
+
+    #[test]
+    fn test_delay_honours_retry_after() {
+        let policy = RetryPolicy::default();
+        assert_eq!(
+            policy.delay(0, Some(Duration::from_secs(7))),
+            Some(Duration::from_secs(7))
+        );
+        assert!(policy.delay(0, Some(Duration::from_secs(3600))).is_none());
+    }
+
+    #[test]
+    fn test_parse_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Thu, 01 Jan 2026 00:00:30 GMT", now),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 31 Dec 2025 23:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable(StatusCode::OK));
+    }
+
+    #[test]
+    fn test_is_idempotent() {
+        assert!(is_idempotent(&[
+            json!(["Email/query", {}, "q0"]),
+            json!(["Email/get", {}, "g0"]),
+            json!(["Mailbox/changes", {}, "c0"]),
+        ]));
+        assert!(!is_idempotent(&[
+            json!(["Email/get", {}, "g0"]),
+            json!(["Email/set", {}, "s0"]),
+        ]));
+        assert!(!is_idempotent(&[json!(["EmailSubmission/set", {}, "s0"])]));
+    }
+}
@@ -17,6 +17,18 @@ use tracing_subscriber::EnvFilter;
 #[command(name = "fastmail-cli")]
 #[command(version, about = "CLI for Fastmail's JMAP API", long_about = None)]
 struct Cli {
+    /// Use a named profile from the config file (also FASTMAIL_PROFILE)
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Act on a shared or delegated account (name or ID, see `list accounts`)
+    #[arg(long, global = true, value_name = "NAME|ID")]
+    account: Option<String>,
+
+    /// Fetch the JMAP session instead of reusing the cached one
+    #[arg(long, global = true)]
+    no_cache: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -25,10 +37,23 @@ struct Cli {
 enum Commands {
     /// Authenticate with Fastmail API token
     Auth {
-        /// API token from Fastmail settings
+        /// API token from Fastmail settings, or with --store command, a command that prints it
         token: String,
+
+        /// Where the token lives: in the config file, or behind a credential helper command
+        #[arg(long, value_enum, default_value = "config")]
+        store: TokenStore,
+
+        /// Find a non-Fastmail JMAP server from an email address or domain
+        /// (via /.well-known/jmap) and save its session URL
+        #[arg(long, value_name = "EMAIL")]
+        discover: Option<String>,
     },
 
+    /// Check the token: user, accounts, granted capabilities, server limits and CardDAV login
+    #[command(alias = "whoami")]
+    Doctor,
+
     /// List resources
     #[command(subcommand)]
     List(ListCommands),
@@ -47,6 +72,10 @@ enum Commands {
 
     /// Search emails with JMAP filters
     Search {
+        /// Query expression, e.g. 'from:alice OR from:bob -subject:newsletter is:unread'
+        #[arg(short, long)]
+        query: Option<String>,
+
         /// Full-text search (from, to, cc, bcc, subject, body)
         #[arg(short, long)]
         text: Option<String>,
@@ -110,6 +139,26 @@ enum Commands {
         /// Maximum results
         #[arg(short, long, default_value = "50")]
         limit: u32,
+
+        /// Skip this many results
+        #[arg(long, default_value = "0", conflicts_with = "page_token")]
+        offset: u32,
+
+        /// Continue from a previous result's next_page_token
+        #[arg(long)]
+        page_token: Option<String>,
+
+        /// Fetch every page, printing one email per JSON line as pages arrive (--limit becomes the page size)
+        #[arg(long)]
+        all: bool,
+
+        /// Sort by receivedAt, sentAt, size, from, to or subject; append :asc for ascending
+        #[arg(long, value_name = "FIELD[:asc]", value_parser = commands::parse_sort_arg)]
+        sort: Option<(&'static str, bool)>,
+
+        /// Show one email per conversation
+        #[arg(long)]
+        collapse_threads: bool,
     },
 
     /// Send an email
@@ -122,9 +171,9 @@ enum Commands {
         #[arg(long)]
         subject: String,
 
-        /// Email body (plain text)
-        #[arg(long)]
-        body: String,
+        /// Email body (plain text, or Markdown with --markdown)
+        #[arg(long, required_unless_present = "html")]
+        body: Option<String>,
 
         /// CC recipient(s), comma-separated
         #[arg(long)]
@@ -137,12 +186,45 @@ enum Commands {
         /// In-Reply-To message ID (for threading)
         #[arg(long)]
         reply_to: Option<String>,
+
+        /// File to attach (repeatable)
+        #[arg(long, value_name = "PATH")]
+        attach: Vec<String>,
+
+        /// HTML body from a file, or "-" for stdin (text fallback is generated unless --body is given)
+        #[arg(long, value_name = "FILE")]
+        html: Option<String>,
+
+        /// Render the body as Markdown and send HTML with a plain-text alternative
+        #[arg(long, conflicts_with = "html")]
+        markdown: bool,
+
+        /// Send from this identity (address or identity ID; see `list identities`)
+        #[arg(long, value_name = "EMAIL|ID")]
+        from: Option<String>,
+
+        /// Append the identity's signature
+        #[arg(long)]
+        signature: bool,
+
+        /// Send at this time (RFC 3339, or "YYYY-MM-DD HH:MM" local time)
+        #[arg(long, value_name = "DATETIME", conflicts_with = "delay")]
+        send_at: Option<String>,
+
+        /// Send after a delay, e.g. 10m, 2h, 1d (cancel with `outbox cancel`)
+        #[arg(long, value_name = "DURATION")]
+        delay: Option<String>,
     },
 
     /// Move email to a mailbox
     Move {
-        /// Email ID
-        email_id: String,
+        /// Email ID(s); "-" reads IDs from stdin
+        #[arg(required_unless_present = "selector")]
+        email_ids: Vec<String>,
+
+        /// Select emails with a query instead, e.g. 'from:notifications@github.com in:Inbox'
+        #[arg(long = "where", value_name = "QUERY")]
+        selector: Option<String>,
 
         /// Destination mailbox name
         #[arg(long)]
@@ -151,8 +233,13 @@ enum Commands {
 
     /// Mark email as spam
     Spam {
-        /// Email ID
-        email_id: String,
+        /// Email ID(s); "-" reads IDs from stdin
+        #[arg(required_unless_present = "selector")]
+        email_ids: Vec<String>,
+
+        /// Select emails with a query instead, e.g. 'from:notifications@github.com in:Inbox'
+        #[arg(long = "where", value_name = "QUERY")]
+        selector: Option<String>,
 
         /// Skip confirmation
         #[arg(short = 'y', long)]
@@ -161,14 +248,53 @@ enum Commands {
 
     /// Mark email as read or unread
     MarkRead {
-        /// Email ID
-        email_id: String,
+        /// Email ID(s); "-" reads IDs from stdin
+        #[arg(required_unless_present = "selector")]
+        email_ids: Vec<String>,
+
+        /// Select emails with a query instead, e.g. 'from:notifications@github.com in:Inbox'
+        #[arg(long = "where", value_name = "QUERY")]
+        selector: Option<String>,
 
         /// Mark as unread instead of read
         #[arg(long)]
         unread: bool,
     },
 
+    /// Flag (star) or unflag emails
+    Flag {
+        /// Email ID(s); "-" reads IDs from stdin
+        #[arg(required_unless_present = "selector")]
+        email_ids: Vec<String>,
+
+        /// Select emails with a query instead, e.g. 'from:notifications@github.com in:Inbox'
+        #[arg(long = "where", value_name = "QUERY")]
+        selector: Option<String>,
+
+        /// Remove the flag instead
+        #[arg(long)]
+        unflag: bool,
+    },
+
+    /// Move emails to Trash, or delete them permanently
+    Delete {
+        /// Email ID(s); "-" reads IDs from stdin
+        #[arg(required_unless_present = "selector")]
+        email_ids: Vec<String>,
+
+        /// Select emails with a query instead, e.g. 'in:Trash before:2024-01-01'
+        #[arg(long = "where", value_name = "QUERY")]
+        selector: Option<String>,
+
+        /// Destroy the emails instead of moving them to Trash (cannot be undone)
+        #[arg(long)]
+        permanent: bool,
+
+        /// Skip confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+
     /// Download attachments from an email
     Download {
         /// Email ID
@@ -192,9 +318,9 @@ enum Commands {
         /// Email ID to reply to
         email_id: String,
 
-        /// Reply body (plain text)
-        #[arg(long)]
-        body: String,
+        /// Reply body (plain text, or Markdown with --markdown)
+        #[arg(long, required_unless_present = "html")]
+        body: Option<String>,
 
         /// Reply to all recipients
         #[arg(long)]
@@ -207,6 +333,34 @@ enum Commands {
         /// BCC recipient(s), comma-separated
         #[arg(long)]
         bcc: Option<String>,
+
+        /// File to attach (repeatable)
+        #[arg(long, value_name = "PATH")]
+        attach: Vec<String>,
+
+        /// HTML body from a file, or "-" for stdin (text fallback is generated unless --body is given)
+        #[arg(long, value_name = "FILE")]
+        html: Option<String>,
+
+        /// Render the body as Markdown and send HTML with a plain-text alternative
+        #[arg(long, conflicts_with = "html")]
+        markdown: bool,
+
+        /// Send from this identity (address or identity ID; see `list identities`)
+        #[arg(long, value_name = "EMAIL|ID")]
+        from: Option<String>,
+
+        /// Append the identity's signature
+        #[arg(long)]
+        signature: bool,
+
+        /// Send at this time (RFC 3339, or "YYYY-MM-DD HH:MM" local time)
+        #[arg(long, value_name = "DATETIME", conflicts_with = "delay")]
+        send_at: Option<String>,
+
+        /// Send after a delay, e.g. 10m, 2h, 1d (cancel with `outbox cancel`)
+        #[arg(long, value_name = "DURATION")]
+        delay: Option<String>,
     },
 
     /// Forward an email
@@ -229,6 +383,38 @@ enum Commands {
         /// BCC recipient(s), comma-separated
         #[arg(long)]
         bcc: Option<String>,
+
+        /// File to attach (repeatable)
+        #[arg(long, value_name = "PATH")]
+        attach: Vec<String>,
+
+        /// HTML body from a file, or "-" for stdin (text fallback is generated unless --body is given)
+        #[arg(long, value_name = "FILE")]
+        html: Option<String>,
+
+        /// Render the body as Markdown and send HTML with a plain-text alternative
+        #[arg(long, conflicts_with = "html")]
+        markdown: bool,
+
+        /// Send from this identity (address or identity ID; see `list identities`)
+        #[arg(long, value_name = "EMAIL|ID")]
+        from: Option<String>,
+
+        /// Append the identity's signature
+        #[arg(long)]
+        signature: bool,
+
+        /// Send at this time (RFC 3339, or "YYYY-MM-DD HH:MM" local time)
+        #[arg(long, value_name = "DATETIME", conflicts_with = "delay")]
+        send_at: Option<String>,
+
+        /// Send after a delay, e.g. 10m, 2h, 1d (cancel with `outbox cancel`)
+        #[arg(long, value_name = "DURATION")]
+        delay: Option<String>,
+
+        /// Attach the original message (message/rfc822) instead of quoting it inline
+        #[arg(long)]
+        as_attachment: bool,
     },
 
     /// Generate shell completions
@@ -238,6 +424,18 @@ enum Commands {
         shell: Shell,
     },
 
+    /// Stage, edit and send drafts
+    #[command(subcommand)]
+    Draft(DraftCommands),
+
+    /// List and cancel scheduled sends that haven't gone out yet
+    #[command(subcommand)]
+    Outbox(OutboxCommands),
+
+    /// Manage mailboxes (folders)
+    #[command(subcommand)]
+    Mailbox(MailboxCommands),
+
     /// Manage masked email addresses
     #[command(subcommand)]
     Masked(MaskedCommands),
@@ -246,10 +444,305 @@ enum Commands {
     #[command(subcommand)]
     Contacts(ContactsCommands),
 
+    /// Show IDs created, updated or destroyed since the last run (or --since)
+    Changes {
+        /// Which objects to track
+        #[arg(value_enum)]
+        data_type: DataType,
+
+        /// State to diff from instead of the stored one
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Don't store the new state
+        #[arg(long, conflicts_with = "reset")]
+        no_save: bool,
+
+        /// Forget the stored state and record the current one
+        #[arg(long, conflicts_with = "since")]
+        reset: bool,
+    },
+
+    /// Stream push notifications as JSON lines until interrupted
+    Watch {
+        /// Print each created, updated or destroyed email instead of raw state changes
+        #[arg(long)]
+        expand: bool,
+
+        /// Only report emails in this mailbox (implies --expand)
+        #[arg(long)]
+        mailbox: Option<String>,
+    },
+
+    /// Run commands for new mail matching the [[hooks]] in the config file
+    #[command(subcommand)]
+    Hooks(HooksCommands),
+
     /// Run as MCP (Model Context Protocol) server for Claude integration
     Mcp,
 }
 
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum TokenStore {
+    /// Save the token in config.toml
+    Config,
+    /// Save the command (e.g. "pass show fastmail/token"); its output is never written
+    Command,
+}
+
+#[derive(Subcommand)]
+enum HooksCommands {
+    /// Wait for new mail and run each matching hook, until interrupted
+    Run,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum DataType {
+    Email,
+    Mailbox,
+    Thread,
+}
+
+impl DataType {
+    fn jmap_name(self) -> &'static str {
+        match self {
+            DataType::Email => "Email",
+            DataType::Mailbox => "Mailbox",
+            DataType::Thread => "Thread",
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum DraftCommands {
+    /// Save a new draft in the Drafts mailbox
+    Create {
+        /// Recipient(s), comma-separated
+        #[arg(long)]
+        to: String,
+
+        /// Subject line
+        #[arg(long)]
+        subject: String,
+
+        /// Email body (plain text, or Markdown with --markdown)
+        #[arg(long, required_unless_present = "html")]
+        body: Option<String>,
+
+        /// CC recipient(s), comma-separated
+        #[arg(long)]
+        cc: Option<String>,
+
+        /// BCC recipient(s), comma-separated
+        #[arg(long)]
+        bcc: Option<String>,
+
+        /// File to attach (repeatable)
+        #[arg(long, value_name = "PATH")]
+        attach: Vec<String>,
+
+        /// HTML body from a file, or "-" for stdin (text fallback is generated unless --body is given)
+        #[arg(long, value_name = "FILE")]
+        html: Option<String>,
+
+        /// Render the body as Markdown and send HTML with a plain-text alternative
+        #[arg(long, conflicts_with = "html")]
+        markdown: bool,
+
+        /// Send from this identity (address or identity ID; see `list identities`)
+        #[arg(long, value_name = "EMAIL|ID")]
+        from: Option<String>,
+
+        /// Append the identity's signature
+        #[arg(long)]
+        signature: bool,
+    },
+
+    /// List drafts
+    List {
+        /// Maximum results
+        #[arg(short, long, default_value = "50")]
+        limit: u32,
+    },
+
+    /// Show a draft
+    Show {
+        /// Draft email ID
+        email_id: String,
+    },
+
+    /// Replace a draft's fields; anything not given is kept
+    Edit {
+        /// Draft email ID
+        email_id: String,
+
+        /// Recipient(s), comma-separated
+        #[arg(long)]
+        to: Option<String>,
+
+        /// Subject line
+        #[arg(long)]
+        subject: Option<String>,
+
+        /// Email body (plain text, or Markdown with --markdown)
+        #[arg(long)]
+        body: Option<String>,
+
+        /// CC recipient(s), comma-separated
+        #[arg(long)]
+        cc: Option<String>,
+
+        /// BCC recipient(s), comma-separated
+        #[arg(long)]
+        bcc: Option<String>,
+
+        /// File to attach, in addition to existing attachments (repeatable)
+        #[arg(long, value_name = "PATH")]
+        attach: Vec<String>,
+
+        /// HTML body from a file, or "-" for stdin (text fallback is generated unless --body is given)
+        #[arg(long, value_name = "FILE")]
+        html: Option<String>,
+
+        /// Render the body as Markdown and send HTML with a plain-text alternative
+        #[arg(long, conflicts_with = "html")]
+        markdown: bool,
+
+        /// Send from this identity (address or identity ID; see `list identities`)
+        #[arg(long, value_name = "EMAIL|ID")]
+        from: Option<String>,
+
+        /// Append the identity's signature
+        #[arg(long)]
+        signature: bool,
+    },
+
+    /// Send a draft and move it to Sent
+    Send {
+        /// Draft email ID
+        email_id: String,
+
+        /// Send at this time (RFC 3339, or "YYYY-MM-DD HH:MM" local time)
+        #[arg(long, value_name = "DATETIME", conflicts_with = "delay")]
+        send_at: Option<String>,
+
+        /// Send after a delay, e.g. 10m, 2h, 1d (cancel with `outbox cancel`)
+        #[arg(long, value_name = "DURATION")]
+        delay: Option<String>,
+    },
+
+    /// Delete a draft
+    Delete {
+        /// Draft email ID
+        email_id: String,
+
+        /// Skip confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum OutboxCommands {
+    /// List pending submissions
+    List,
+
+    /// Cancel a pending submission and move its email back to Drafts
+    Cancel {
+        /// Submission ID (from `outbox list`)
+        submission_id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum MailboxCommands {
+    /// Create a mailbox
+    Create {
+        /// Mailbox name, or a path like Clients/Acme to create inside an existing mailbox
+        name: String,
+
+        /// Create it inside this mailbox
+        #[arg(long)]
+        parent: Option<String>,
+    },
+
+    /// Rename a mailbox, keeping its place in the tree
+    Rename {
+        /// Current mailbox name
+        name: String,
+
+        /// New name
+        new_name: String,
+    },
+
+    /// Move a mailbox under another one, or to the top level
+    Move {
+        /// Mailbox name
+        name: String,
+
+        /// New parent mailbox
+        #[arg(long, required_unless_present = "top_level")]
+        to: Option<String>,
+
+        /// Move to the top level instead
+        #[arg(long, conflicts_with = "to")]
+        top_level: bool,
+    },
+
+    /// Subscribe to a mailbox so mail clients show it
+    Subscribe {
+        /// Mailbox name
+        name: String,
+    },
+
+    /// Unsubscribe from a mailbox
+    Unsubscribe {
+        /// Mailbox name
+        name: String,
+    },
+
+    /// Assign a role to a mailbox, e.g. use a custom folder as the archive
+    Role {
+        /// Mailbox name
+        name: String,
+
+        /// Role to assign
+        #[arg(
+            required_unless_present = "clear",
+            value_parser = ["inbox", "archive", "drafts", "sent", "trash", "junk", "important", "flagged", "all", "subscribed"]
+        )]
+        role: Option<String>,
+
+        /// Remove the mailbox's role instead
+        #[arg(long, conflicts_with = "role")]
+        clear: bool,
+    },
+
+    /// Delete a mailbox
+    Delete {
+        /// Mailbox name
+        name: String,
+
+        /// Also delete emails that are only in this mailbox (otherwise it must be empty)
+        #[arg(long)]
+        remove_emails: bool,
+
+        /// Skip confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+
+    /// Empty a mailbox, e.g. Trash or Junk: deletes emails only in it, keeps those filed elsewhere
+    Empty {
+        /// Mailbox name or role
+        name: String,
+
+        /// Skip confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+    },
+}
+
 #[derive(Subcommand)]
 enum MaskedCommands {
     /// List all masked email addresses
@@ -296,7 +789,17 @@ enum MaskedCommands {
 #[derive(Subcommand)]
 enum ListCommands {
     /// List mailboxes (folders)
-    Mailboxes,
+    Mailboxes {
+        /// Nest mailboxes under their parents, with full paths
+        #[arg(long)]
+        tree: bool,
+    },
+
+    /// List sending identities and their signatures
+    Identities,
+
+    /// List accounts this token can access, including shared ones
+    Accounts,
 
     /// List emails in a mailbox
     Emails {
@@ -307,6 +810,26 @@ enum ListCommands {
         /// Maximum results
         #[arg(short, long, default_value = "50")]
         limit: u32,
+
+        /// Skip this many results
+        #[arg(long, default_value = "0", conflicts_with = "page_token")]
+        offset: u32,
+
+        /// Continue from a previous result's next_page_token
+        #[arg(long)]
+        page_token: Option<String>,
+
+        /// Fetch every page, printing one email per JSON line as pages arrive (--limit becomes the page size)
+        #[arg(long)]
+        all: bool,
+
+        /// Sort by receivedAt, sentAt, size, from, to or subject; append :asc for ascending
+        #[arg(long, value_name = "FIELD[:asc]", value_parser = commands::parse_sort_arg)]
+        sort: Option<(&'static str, bool)>,
+
+        /// Show one email per conversation
+        #[arg(long)]
+        collapse_threads: bool,
     },
 }
 
@@ -330,20 +853,87 @@ async fn main() {
         .init();
 
     let cli = Cli::parse();
+    // Completions don't need the config file, so a broken one can't stop them
+    if let Commands::Completions { shell } = cli.command {
+        generate(
+            shell,
+            &mut Cli::command(),
+            "fastmail-cli",
+            &mut io::stdout(),
+        );
+        return;
+    }
+
+    let mut config = match config::Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            Output::<()>::error(e.to_string()).print();
+            std::process::exit(1);
+        }
+    };
+    if cli.no_cache {
+        config.disable_session_cache();
+    }
+    if let Some(profile) = cli.profile {
+        config.set_profile_override(profile);
+    }
+    if let Some(account) = cli.account {
+        config.set_account_override(account);
+    }
 
     let result = match cli.command {
-        Commands::Auth { token } => commands::auth(&token).await,
+        Commands::Auth {
+            token,
+            store,
+            discover,
+        } => {
+            commands::auth(
+                config,
+                &token,
+                matches!(store, TokenStore::Command),
+                discover.as_deref(),
+            )
+            .await
+        }
+
+        Commands::Doctor => commands::doctor(&config).await,
 
         Commands::List(cmd) => match cmd {
-            ListCommands::Mailboxes => commands::list_mailboxes().await,
-            ListCommands::Emails { mailbox, limit } => commands::list_emails(&mailbox, limit).await,
+            ListCommands::Mailboxes { tree } => commands::list_mailboxes(&config, tree).await,
+            ListCommands::Identities => commands::list_identities(&config).await,
+            ListCommands::Accounts => commands::list_accounts(&config).await,
+            ListCommands::Emails {
+                mailbox,
+                limit,
+                offset,
+                page_token,
+                all,
+                sort,
+                collapse_threads,
+            } => {
+                commands::list_emails(
+                    &config,
+                    &mailbox,
+                    &commands::QueryOptions {
+                        limit,
+                        offset,
+                        page_token,
+                        all,
+                        sort: sort.map_or("receivedAt", |(p, _)| p),
+                        ascending: sort.is_some_and(|(_, asc)| asc),
+                        collapse_threads,
+                    },
+                )
+                .await
+            }
         },
 
-        Commands::Get { email_id } => commands::get_email(&email_id).await,
+        Commands::Get { email_id } => commands::get_email(&config, &email_id).await,
 
-        Commands::Thread { email_id } => commands::get_thread(&email_id).await,
+        Commands::Thread { email_id } => commands::get_thread(&config, &email_id).await,
 
         Commands::Search {
+            query,
             text,
             from,
             to,
@@ -360,9 +950,16 @@ async fn main() {
             unread,
             flagged,
             limit,
+            offset,
+            page_token,
+            all,
+            sort,
+            collapse_threads,
         } => {
             commands::search(
+                &config,
                 commands::SearchFilter {
+                    query,
                     text,
                     from,
                     to,
@@ -379,7 +976,15 @@ async fn main() {
                     unread,
                     flagged,
                 },
-                limit,
+                &commands::QueryOptions {
+                    limit,
+                    offset,
+                    page_token,
+                    all,
+                    sort: sort.map_or("receivedAt", |(p, _)| p),
+                    ascending: sort.is_some_and(|(_, asc)| asc),
+                    collapse_threads,
+                },
             )
             .await
         }
@@ -391,29 +996,129 @@ async fn main() {
             cc,
             bcc,
             reply_to,
+            attach,
+            html,
+            markdown,
+            from,
+            signature,
+            send_at,
+            delay,
         } => {
             commands::send(
+                &config,
                 &to,
                 &subject,
-                &body,
+                body.as_deref().unwrap_or(""),
                 cc.as_deref(),
                 bcc.as_deref(),
                 reply_to.as_deref(),
+                &commands::ComposeInput {
+                    attach,
+                    html,
+                    markdown,
+                    from,
+                    signature,
+                    send_at,
+                    delay,
+                },
             )
             .await
         }
 
-        Commands::Move { email_id, to } => commands::move_email(&email_id, &to).await,
+        Commands::Move {
+            email_ids,
+            selector,
+            to,
+        } => {
+            let selection = commands::EmailSelection {
+                ids: email_ids,
+                query: selector,
+            };
+            commands::move_email(&config, &selection, &to).await
+        }
 
-        Commands::Spam { email_id, yes } => {
+        Commands::Spam {
+            email_ids,
+            selector,
+            yes,
+        } => {
             if !yes {
-                eprintln!("Mark email {} as spam? Use -y to confirm.", email_id);
+                match selector {
+                    Some(ref query) => {
+                        eprintln!(
+                            "Mark all emails matching '{}' as spam? Use -y to confirm.",
+                            query
+                        )
+                    }
+                    None => eprintln!(
+                        "Mark email {} as spam? Use -y to confirm.",
+                        email_ids.join(", ")
+                    ),
+                }
                 std::process::exit(1);
             }
-            commands::mark_spam(&email_id).await
+            let selection = commands::EmailSelection {
+                ids: email_ids,
+                query: selector,
+            };
+            commands::mark_spam(&config, &selection).await
+        }
+
+        Commands::MarkRead {
+            email_ids,
+            selector,
+            unread,
+        } => {
+            let selection = commands::EmailSelection {
+                ids: email_ids,
+                query: selector,
+            };
+            commands::mark_read(&config, &selection, !unread).await
+        }
+
+        Commands::Flag {
+            email_ids,
+            selector,
+            unflag,
+        } => {
+            let selection = commands::EmailSelection {
+                ids: email_ids,
+                query: selector,
+            };
+            commands::flag(&config, &selection, !unflag).await
         }
 
-        Commands::MarkRead { email_id, unread } => commands::mark_read(&email_id, !unread).await,
+        Commands::Delete {
+            email_ids,
+            selector,
+            permanent,
+            yes,
+        } => {
+            if !yes {
+                let action = if permanent {
+                    "Permanently delete"
+                } else {
+                    "Move to Trash"
+                };
+                match selector {
+                    Some(ref query) => eprintln!(
+                        "{} all emails matching '{}'? Use -y to confirm.",
+                        action, query
+                    ),
+                    None => eprintln!(
+                        "{} email {}? Use -y to confirm.",
+                        action,
+                        email_ids.join(", ")
+                    ),
+                }
+                std::process::exit(1);
+            }
+            let selection = commands::EmailSelection {
+                ids: email_ids,
+                query: selector,
+            };
+            commands::delete(&config, &selection, permanent).await
+        }
 
         Commands::Download {
             email_id,
@@ -422,6 +1127,7 @@ async fn main() {
             max_size,
         } => {
             commands::download_attachment(
+                &config,
                 &email_id,
                 output.as_deref(),
                 format.as_deref(),
@@ -436,7 +1142,33 @@ async fn main() {
             all,
             cc,
             bcc,
-        } => commands::reply(&email_id, &body, all, cc.as_deref(), bcc.as_deref()).await,
+            attach,
+            html,
+            markdown,
+            from,
+            signature,
+            send_at,
+            delay,
+        } => {
+            commands::reply(
+                &config,
+                &email_id,
+                body.as_deref().unwrap_or(""),
+                all,
+                cc.as_deref(),
+                bcc.as_deref(),
+                &commands::ComposeInput {
+                    attach,
+                    html,
+                    markdown,
+                    from,
+                    signature,
+                    send_at,
+                    delay,
+                },
+            )
+            .await
+        }
 
         Commands::Forward {
             email_id,
@@ -444,49 +1176,236 @@ async fn main() {
             body,
             cc,
             bcc,
-        } => commands::forward(&email_id, &to, &body, cc.as_deref(), bcc.as_deref()).await,
-
-        Commands::Completions { shell } => {
-            generate(
-                shell,
-                &mut Cli::command(),
-                "fastmail-cli",
-                &mut io::stdout(),
-            );
-            return;
+            attach,
+            html,
+            markdown,
+            from,
+            signature,
+            send_at,
+            delay,
+            as_attachment,
+        } => {
+            let mode = if as_attachment {
+                commands::ForwardMode::Attachment
+            } else {
+                commands::ForwardMode::Inline
+            };
+            commands::forward(
+                &config,
+                &email_id,
+                &to,
+                &body,
+                cc.as_deref(),
+                bcc.as_deref(),
+                mode,
+                &commands::ComposeInput {
+                    attach,
+                    html,
+                    markdown,
+                    from,
+                    signature,
+                    send_at,
+                    delay,
+                },
+            )
+            .await
         }
 
+        Commands::Completions { .. } => unreachable!("handled before loading the config"),
+
+        Commands::Draft(cmd) => match cmd {
+            DraftCommands::Create {
+                to,
+                subject,
+                body,
+                cc,
+                bcc,
+                attach,
+                html,
+                markdown,
+                from,
+                signature,
+            } => {
+                commands::draft_create(
+                    &config,
+                    &to,
+                    &subject,
+                    body.as_deref().unwrap_or(""),
+                    cc.as_deref(),
+                    bcc.as_deref(),
+                    &commands::ComposeInput {
+                        attach,
+                        html,
+                        markdown,
+                        from,
+                        signature,
+                        ..Default::default()
+                    },
+                )
+                .await
+            }
+            DraftCommands::List { limit } => commands::draft_list(&config, limit).await,
+            DraftCommands::Show { email_id } => commands::draft_show(&config, &email_id).await,
+            DraftCommands::Edit {
+                email_id,
+                to,
+                subject,
+                body,
+                cc,
+                bcc,
+                attach,
+                html,
+                markdown,
+                from,
+                signature,
+            } => {
+                commands::draft_edit(
+                    &config,
+                    &email_id,
+                    to.as_deref(),
+                    subject.as_deref(),
+                    body.as_deref(),
+                    cc.as_deref(),
+                    bcc.as_deref(),
+                    &commands::ComposeInput {
+                        attach,
+                        html,
+                        markdown,
+                        from,
+                        signature,
+                        ..Default::default()
+                    },
+                )
+                .await
+            }
+            DraftCommands::Send {
+                email_id,
+                send_at,
+                delay,
+            } => {
+                commands::draft_send(&config, &email_id, send_at.as_deref(), delay.as_deref()).await
+            }
+            DraftCommands::Delete { email_id, yes } => {
+                if !yes {
+                    eprintln!("Delete draft {}? Use -y to confirm.", email_id);
+                    std::process::exit(1);
+                }
+                commands::draft_delete(&config, &email_id).await
+            }
+        },
+
+        Commands::Outbox(cmd) => match cmd {
+            OutboxCommands::List => commands::outbox_list(&config).await,
+            OutboxCommands::Cancel { submission_id } => {
+                commands::outbox_cancel(&config, &submission_id).await
+            }
+        },
+
+        Commands::Mailbox(cmd) => match cmd {
+            MailboxCommands::Create { name, parent } => {
+                commands::mailbox_create(&config, &name, parent.as_deref()).await
+            }
+            MailboxCommands::Rename { name, new_name } => {
+                commands::mailbox_rename(&config, &name, &new_name).await
+            }
+            MailboxCommands::Move { name, to, .. } => {
+                commands::mailbox_move(&config, &name, to.as_deref()).await
+            }
+            MailboxCommands::Subscribe { name } => {
+                commands::mailbox_subscribe(&config, &name, true).await
+            }
+            MailboxCommands::Unsubscribe { name } => {
+                commands::mailbox_subscribe(&config, &name, false).await
+            }
+            MailboxCommands::Role { name, role, .. } => {
+                commands::mailbox_set_role(&config, &name, role.as_deref()).await
+            }
+            MailboxCommands::Delete {
+                name,
+                remove_emails,
+                yes,
+            } => {
+                if !yes {
+                    if remove_emails {
+                        eprintln!(
+                            "Delete mailbox '{}' and every email only filed there? Use -y to confirm.",
+                            name
+                        );
+                    } else {
+                        eprintln!("Delete mailbox '{}'? Use -y to confirm.", name);
+                    }
+                    std::process::exit(1);
+                }
+                commands::mailbox_delete(&config, &name, remove_emails).await
+            }
+            MailboxCommands::Empty { name, yes } => {
+                if !yes {
+                    eprintln!(
+                        "Permanently delete the emails in '{}' (emails also in other mailboxes are only removed from it)? Use -y to confirm.",
+                        name
+                    );
+                    std::process::exit(1);
+                }
+                commands::empty_mailbox(&config, &name).await
+            }
+        },
+
         Commands::Masked(cmd) => match cmd {
-            MaskedCommands::List => commands::list_masked_emails().await,
+            MaskedCommands::List => commands::list_masked_emails(&config).await,
             MaskedCommands::Create {
                 domain,
                 description,
                 prefix,
             } => {
                 commands::create_masked_email(
+                    &config,
                     domain.as_deref(),
                     description.as_deref(),
                     prefix.as_deref(),
                 )
                 .await
             }
-            MaskedCommands::Enable { id } => commands::enable_masked_email(&id).await,
-            MaskedCommands::Disable { id } => commands::disable_masked_email(&id).await,
+            MaskedCommands::Enable { id } => commands::enable_masked_email(&config, &id).await,
+            MaskedCommands::Disable { id } => commands::disable_masked_email(&config, &id).await,
             MaskedCommands::Delete { id, yes } => {
                 if !yes {
                     eprintln!("Delete masked email {}? Use -y to confirm.", id);
                     std::process::exit(1);
                 }
-                commands::delete_masked_email(&id).await
+                commands::delete_masked_email(&config, &id).await
             }
         },
 
         Commands::Contacts(cmd) => match cmd {
-            ContactsCommands::List => commands::list_contacts().await,
-            ContactsCommands::Search { query } => commands::search_contacts(&query).await,
+            ContactsCommands::List => commands::list_contacts(&config).await,
+            ContactsCommands::Search { query } => commands::search_contacts(&config, &query).await,
+        },
+
+        Commands::Changes {
+            data_type,
+            since,
+            no_save,
+            reset,
+        } => {
+            commands::changes(
+                &config,
+                data_type.jmap_name(),
+                since.as_deref(),
+                !no_save,
+                reset,
+            )
+            .await
+        }
+
+        Commands::Watch { expand, mailbox } => {
+            commands::watch(&config, expand, mailbox.as_deref()).await
+        }
+
+        Commands::Hooks(cmd) => match cmd {
+            HooksCommands::Run => commands::hooks_run(&config).await,
         },
 
-        Commands::Mcp => mcp::run_server().await,
+        Commands::Mcp => mcp::run_server(config).await,
     };
 
     if let Err(e) = result {
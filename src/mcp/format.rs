@@ -1,7 +1,7 @@
 //! Formatting helpers for MCP tool output
 
 use crate::carddav::Contact;
-use crate::models::{Email, EmailAddress, Mailbox, MaskedEmail};
+use crate::models::{Email, EmailAddress, EmailPage, Identity, Mailbox, MaskedEmail};
 
 pub fn format_address(addr: &EmailAddress) -> String {
     match &addr.name {
@@ -22,20 +22,25 @@ pub fn format_address_list(addrs: Option<&Vec<EmailAddress>>) -> String {
     }
 }
 
-pub fn format_mailbox(m: &Mailbox) -> String {
+pub fn format_mailbox(m: &Mailbox, path: &str) -> String {
     let role = m
         .role
         .as_ref()
         .map(|r| format!(" [{}]", r))
         .unwrap_or_default();
+    let read_only = if m.may_add_items() {
+        ""
+    } else {
+        " [read-only]"
+    };
     let unread = if m.unread_emails > 0 {
         format!(" ({} unread)", m.unread_emails)
     } else {
         String::new()
     };
     format!(
-        "{}{}{} - {} emails (id: {})",
-        m.name, role, unread, m.total_emails, m.id
+        "{}{}{}{} - {} emails (id: {})",
+        path, role, read_only, unread, m.total_emails, m.id
     )
 }
 
@@ -66,6 +71,32 @@ pub fn format_email_summary(e: &Email) -> String {
     )
 }
 
+/// Email summaries followed by the range shown and a cursor for the next page
+pub fn format_email_page(page: &EmailPage) -> String {
+    let summaries = page
+        .emails
+        .iter()
+        .map(format_email_summary)
+        .collect::<Vec<_>>()
+        .join("\n\n---\n\n");
+
+    let first = page.position + 1;
+    let last = page.position + page.emails.len() as u64;
+    let range = match page.total {
+        Some(total) => format!("Showing {}-{} of {}", first, last, total),
+        None => format!("Showing {}-{}", first, last),
+    };
+    let more = match page.next_page_token {
+        Some(ref cursor) => format!(
+            "More results available: call again with cursor=\"{}\"",
+            cursor
+        ),
+        None => "No more results.".to_string(),
+    };
+
+    format!("{}\n\n---\n{}. {}", summaries, range, more)
+}
+
 pub fn format_email_full(e: &Email) -> String {
     let from = format_address_list(e.from.as_ref());
     let to = format_address_list(e.to.as_ref());
@@ -98,6 +129,42 @@ pub fn format_email_full(e: &Email) -> String {
     )
 }
 
+pub fn format_identity_address(i: &Identity) -> String {
+    format_address(&EmailAddress {
+        name: Some(i.name.clone()),
+        email: i.email.clone(),
+    })
+}
+
+pub fn format_identity(i: &Identity) -> String {
+    let mut lines = vec![format_identity_address(i), format!("ID: {}", i.id)];
+
+    if i.is_wildcard() {
+        lines.push("Wildcard: sends as any address at this domain".to_string());
+    }
+    if let Some(ref reply_to) = i.reply_to
+        && !reply_to.is_empty()
+    {
+        lines.push(format!("Reply-To: {}", format_address_list(Some(reply_to))));
+    }
+    if let Some(ref sig) = i.text_signature
+        && !sig.is_empty()
+    {
+        lines.push(format!("Signature:\n{}", sig));
+    }
+
+    lines.join("\n")
+}
+
+/// Preview line for the signature option of send/reply/forward
+pub fn format_signature_preview(i: &Identity, enabled: bool) -> String {
+    match i.text_signature.as_deref() {
+        Some(sig) if enabled && !sig.is_empty() => format!("appended\n{}", sig),
+        _ if enabled => "(identity has no signature)".to_string(),
+        _ => "(none)".to_string(),
+    }
+}
+
 pub fn format_masked_email(m: &MaskedEmail) -> String {
     let state = m.state.as_deref().unwrap_or("unknown");
     let state_indicator = match state {
@@ -13,10 +13,14 @@ use rmcp::{
 use tokio::sync::Mutex;
 
 use crate::carddav::CardDavClient;
+use crate::commands::{ComposeOptions, ForwardMode, QueryOptions, parse_sort_arg};
 use crate::config::Config;
 use crate::jmap::JmapClient;
 use crate::models::EmailAddress;
-use crate::util::{MCP_IMAGE_MAX_BYTES, extract_text, infer_image_mime, is_image, resize_image};
+use crate::util::{
+    MCP_IMAGE_MAX_BYTES, extract_text, html_to_text, infer_image_mime, is_image, markdown_to_html,
+    resize_image,
+};
 
 type ToolResult = std::result::Result<CallToolResult, McpError>;
 
@@ -27,11 +31,20 @@ use format::*;
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 pub struct ListEmailsRequest {
-    /// Mailbox name (e.g., 'INBOX', 'Sent', 'Archive') or role (e.g., 'inbox', 'sent', 'drafts', 'trash', 'junk')
+    /// Mailbox name or path (e.g., 'INBOX', 'Archive', 'Clients/Acme') or role (e.g., 'inbox', 'sent', 'drafts', 'trash', 'junk')
     pub mailbox: String,
     /// Maximum number of emails to return (default 25, max 100)
     #[serde(default)]
     pub limit: Option<u32>,
+    /// Cursor from a previous response to fetch the next page
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Sort by receivedAt (default), sentAt, size, from, to or subject; append ':asc' for ascending (e.g. 'size' for largest first)
+    #[serde(default)]
+    pub sort: Option<String>,
+    /// Return one email per conversation
+    #[serde(default)]
+    pub collapse_threads: Option<bool>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
@@ -45,6 +58,12 @@ pub struct SearchEmailsRequest {
     /// General search - searches subject, body, from, and to fields
     #[serde(default)]
     pub query: Option<String>,
+    /// Search expression. Plain words search subject, body, from and to. Also supports
+    /// from:/to:/cc:/subject:/body:, in:Mailbox, has:attachment, is:unread/read/flagged,
+    /// before:/after:YYYY-MM-DD, larger:/smaller:5M, OR, -term or NOT term for negation,
+    /// parentheses and "quoted phrases". Example: from:alice OR from:bob -subject:newsletter
+    #[serde(default)]
+    pub expression: Option<String>,
     /// Search sender address/name
     #[serde(default)]
     pub from: Option<String>,
@@ -81,13 +100,22 @@ pub struct SearchEmailsRequest {
     /// Maximum number of results (default 25, max 100)
     #[serde(default)]
     pub limit: Option<u32>,
+    /// Cursor from a previous response to fetch the next page of results
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// Sort by receivedAt (default), sentAt, size, from, to or subject; append ':asc' for ascending (e.g. 'size' for largest first)
+    #[serde(default)]
+    pub sort: Option<String>,
+    /// Return one email per conversation
+    #[serde(default)]
+    pub collapse_threads: Option<bool>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 pub struct MoveEmailRequest {
     /// The email ID to move
     pub email_id: String,
-    /// Target mailbox name (e.g., 'Archive', 'Trash') or role (e.g., 'archive', 'trash')
+    /// Target mailbox name or path (e.g., 'Archive', 'Clients/Acme') or role (e.g., 'archive', 'trash')
     pub target_mailbox: String,
 }
 
@@ -108,6 +136,25 @@ pub struct MarkAsSpamRequest {
     pub action: String,
 }
 
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct DeleteEmailRequest {
+    /// The email ID to delete
+    pub email_id: String,
+    /// true to destroy the email instead of moving it to Trash (cannot be undone)
+    #[serde(default)]
+    pub permanent: Option<bool>,
+    /// 'preview' first to see what will happen, then 'confirm' after user approval
+    pub action: String,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct EmptyMailboxRequest {
+    /// Mailbox name (e.g., 'Trash', 'Junk') or role (e.g., 'trash', 'junk')
+    pub mailbox: String,
+    /// 'preview' first to see what will happen, then 'confirm' after user approval
+    pub action: String,
+}
+
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 pub struct SendEmailRequest {
     /// 'preview' to see the draft, 'confirm' to send - ALWAYS preview first
@@ -124,6 +171,18 @@ pub struct SendEmailRequest {
     /// BCC recipients (hidden), comma-separated
     #[serde(default)]
     pub bcc: Option<String>,
+    /// Local file paths to attach
+    #[serde(default)]
+    pub attachments: Option<Vec<String>>,
+    /// Body format: 'text' (default), 'markdown' (rendered to HTML with a text alternative) or 'html'
+    #[serde(default)]
+    pub body_format: Option<String>,
+    /// Send from this identity (email address or identity ID; see list_identities)
+    #[serde(default)]
+    pub from: Option<String>,
+    /// Append the identity's signature to the body
+    #[serde(default)]
+    pub signature: Option<bool>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
@@ -143,6 +202,15 @@ pub struct ReplyEmailRequest {
     /// BCC recipients (hidden), comma-separated
     #[serde(default)]
     pub bcc: Option<String>,
+    /// Body format: 'text' (default), 'markdown' (rendered to HTML with a text alternative) or 'html'
+    #[serde(default)]
+    pub body_format: Option<String>,
+    /// Send from this identity (email address or identity ID; defaults to the identity the original was sent to)
+    #[serde(default)]
+    pub from: Option<String>,
+    /// Append the identity's signature to the body
+    #[serde(default)]
+    pub signature: Option<bool>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
@@ -162,6 +230,46 @@ pub struct ForwardEmailRequest {
     /// BCC recipients (hidden), comma-separated
     #[serde(default)]
     pub bcc: Option<String>,
+    /// Attach the original message as a .eml file instead of quoting it inline
+    #[serde(default)]
+    pub as_attachment: Option<bool>,
+    /// Body format: 'text' (default), 'markdown' (rendered to HTML with a text alternative) or 'html'
+    #[serde(default)]
+    pub body_format: Option<String>,
+    /// Send from this identity (email address or identity ID; see list_identities)
+    #[serde(default)]
+    pub from: Option<String>,
+    /// Append the identity's signature to the body
+    #[serde(default)]
+    pub signature: Option<bool>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct SaveDraftRequest {
+    /// Recipient email address(es), comma-separated
+    pub to: String,
+    /// Email subject line
+    pub subject: String,
+    /// Email body text
+    pub body: String,
+    /// CC recipients, comma-separated
+    #[serde(default)]
+    pub cc: Option<String>,
+    /// BCC recipients (hidden), comma-separated
+    #[serde(default)]
+    pub bcc: Option<String>,
+    /// Local file paths to attach
+    #[serde(default)]
+    pub attachments: Option<Vec<String>>,
+    /// Body format: 'text' (default), 'markdown' (rendered to HTML with a text alternative) or 'html'
+    #[serde(default)]
+    pub body_format: Option<String>,
+    /// Send from this identity (email address or identity ID; see list_identities)
+    #[serde(default)]
+    pub from: Option<String>,
+    /// Append the identity's signature to the body
+    #[serde(default)]
+    pub signature: Option<bool>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
@@ -208,19 +316,18 @@ pub struct SearchContactsRequest {
 #[derive(Clone)]
 pub struct FastmailMcp {
     client: Arc<Mutex<JmapClient>>,
+    config: Arc<Config>,
     tool_router: ToolRouter<Self>,
 }
 
 impl FastmailMcp {
-    pub async fn new() -> anyhow::Result<Self> {
-        let config = Config::load()?;
-        let token = config.get_token()?;
-
-        let mut client = JmapClient::new(token);
+    pub async fn new(config: Config) -> anyhow::Result<Self> {
+        let mut client = JmapClient::from_config(&config)?;
         client.authenticate().await?;
 
         Ok(Self {
             client: Arc::new(Mutex::new(client)),
+            config: Arc::new(config),
             tool_router: Self::tool_router(),
         })
     }
@@ -233,6 +340,22 @@ impl FastmailMcp {
         Ok(CallToolResult::error(vec![Content::text(msg.into())]))
     }
 
+    /// Render a tool's body according to its body_format, returning (text, html)
+    async fn render_body(
+        body: &str,
+        format: Option<&str>,
+    ) -> std::result::Result<(String, Option<String>), String> {
+        match format.unwrap_or("text") {
+            "text" => Ok((body.to_string(), None)),
+            "markdown" => Ok((body.to_string(), Some(markdown_to_html(body)))),
+            "html" => Ok((html_to_text(body).await, Some(body.to_string()))),
+            other => Err(format!(
+                "Unknown body_format: {} (expected text, markdown or html)",
+                other
+            )),
+        }
+    }
+
     fn parse_addresses(s: &str) -> Vec<EmailAddress> {
         s.split(',')
             .map(|e| EmailAddress {
@@ -253,18 +376,20 @@ impl FastmailMcp {
     async fn list_mailboxes(&self) -> ToolResult {
         let client = self.client.lock().await;
         match client.list_mailboxes().await {
-            Ok(mut mailboxes) => {
-                mailboxes.sort_by(|a, b| {
+            Ok(mailboxes) => {
+                let mut listed: Vec<_> =
+                    mailboxes.iter().map(|m| (m, m.path(&mailboxes))).collect();
+                listed.sort_by(|(a, a_path), (b, b_path)| {
                     // Put role-based mailboxes first
                     match (&a.role, &b.role) {
                         (Some(_), None) => std::cmp::Ordering::Less,
                         (None, Some(_)) => std::cmp::Ordering::Greater,
-                        _ => a.name.cmp(&b.name),
+                        _ => a_path.cmp(b_path),
                     }
                 });
-                let text = mailboxes
+                let text = listed
                     .iter()
-                    .map(format_mailbox)
+                    .map(|(m, path)| format_mailbox(m, path))
                     .collect::<Vec<_>>()
                     .join("\n");
                 Self::text_result(text)
@@ -273,25 +398,49 @@ impl FastmailMcp {
         }
     }
 
+    #[tool(
+        description = "List the sending identities (addresses and aliases) with their signatures. Use the email or ID as 'from' when sending, replying or forwarding."
+    )]
+    async fn list_identities(&self) -> ToolResult {
+        let client = self.client.lock().await;
+        match client.list_identities().await {
+            Ok(identities) => {
+                let text = identities
+                    .iter()
+                    .map(format_identity)
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                Self::text_result(text)
+            }
+            Err(e) => Self::error_result(format!("Failed to list identities: {}", e)),
+        }
+    }
+
     #[tool(
         description = "List emails in a specific mailbox/folder. Returns email summaries with ID, from, subject, date, and preview. Use the email ID with get_email for full content."
     )]
     async fn list_emails(&self, Parameters(req): Parameters<ListEmailsRequest>) -> ToolResult {
         let client = self.client.lock().await;
-        let limit = req.limit.unwrap_or(25).min(100);
+        let (sort, ascending) = match req.sort.as_deref().map(parse_sort_arg).transpose() {
+            Ok(sort) => sort.unwrap_or(("receivedAt", false)),
+            Err(e) => return Self::error_result(e),
+        };
+        let page = QueryOptions {
+            limit: req.limit.unwrap_or(25).min(100),
+            page_token: req.cursor,
+            sort,
+            ascending,
+            collapse_threads: req.collapse_threads.unwrap_or(false),
+            ..Default::default()
+        };
 
         match client.find_mailbox(&req.mailbox).await {
-            Ok(mailbox) => match client.list_emails(&mailbox.id, limit).await {
-                Ok(emails) => {
-                    if emails.is_empty() {
+            Ok(mailbox) => match client.list_emails(&mailbox.id, &page).await {
+                Ok(result) => {
+                    if result.emails.is_empty() {
                         return Self::text_result(format!("No emails in {}", req.mailbox));
                     }
-                    let text = emails
-                        .iter()
-                        .map(format_email_summary)
-                        .collect::<Vec<_>>()
-                        .join("\n\n---\n\n");
-                    Self::text_result(text)
+                    Self::text_result(format_email_page(&result))
                 }
                 Err(e) => Self::error_result(format!("Failed to list emails: {}", e)),
             },
@@ -347,14 +496,26 @@ impl FastmailMcp {
     }
 
     #[tool(
-        description = "Search for emails with flexible filters. Use 'query' for general search, or specific fields for precise filtering. Supports date ranges, attachment filtering, unread/flagged status."
+        description = "Search for emails with flexible filters. Use 'query' for general text search, 'expression' for a search expression with OR, negation and grouping (e.g. 'from:alice OR from:bob -subject:newsletter in:Archive'), or specific fields for precise filtering. Supports date ranges, attachment filtering, unread/flagged status."
     )]
     async fn search_emails(&self, Parameters(req): Parameters<SearchEmailsRequest>) -> ToolResult {
         let client = self.client.lock().await;
-        let limit = req.limit.unwrap_or(25).min(100);
+        let (sort, ascending) = match req.sort.as_deref().map(parse_sort_arg).transpose() {
+            Ok(sort) => sort.unwrap_or(("receivedAt", false)),
+            Err(e) => return Self::error_result(e),
+        };
+        let page = QueryOptions {
+            limit: req.limit.unwrap_or(25).min(100),
+            page_token: req.cursor,
+            sort,
+            ascending,
+            collapse_threads: req.collapse_threads.unwrap_or(false),
+            ..Default::default()
+        };
 
         // Build search filter
         let filter = crate::commands::SearchFilter {
+            query: req.expression,
             text: req.query,
             from: req.from,
             to: req.to,
@@ -383,19 +544,14 @@ impl FastmailMcp {
         };
 
         match client
-            .search_emails_filtered(&filter, mailbox_id.as_deref(), limit)
+            .search_emails_filtered(&filter, mailbox_id.as_deref(), &page)
             .await
         {
-            Ok(emails) => {
-                if emails.is_empty() {
+            Ok(result) => {
+                if result.emails.is_empty() {
                     return Self::text_result("No emails found.");
                 }
-                let text = emails
-                    .iter()
-                    .map(format_email_summary)
-                    .collect::<Vec<_>>()
-                    .join("\n\n---\n\n");
-                Self::text_result(text)
+                Self::text_result(format_email_page(&result))
             }
             Err(e) => Self::error_result(format!("Search failed: {}", e)),
         }
@@ -500,6 +656,111 @@ impl FastmailMcp {
         }
     }
 
+    #[tool(
+        description = "Delete an email. Moves it to Trash by default; permanent=true destroys it and cannot be undone. MUST use action='preview' first, then 'confirm' after user approval."
+    )]
+    async fn delete_email(&self, Parameters(req): Parameters<DeleteEmailRequest>) -> ToolResult {
+        let client = self.client.lock().await;
+        let permanent = req.permanent.unwrap_or(false);
+
+        let email = match client.get_email(&req.email_id).await {
+            Ok(e) => e,
+            Err(e) => return Self::error_result(format!("Email not found: {}", e)),
+        };
+
+        if req.action == "preview" {
+            let effect = if permanent {
+                "Permanently delete the email - this CANNOT be undone"
+            } else {
+                "Move the email to Trash"
+            };
+            return Self::text_result(format!(
+                "DELETE PREVIEW - This will:\n\
+                {}\n\n\
+                Email: \"{}\"\n\
+                From: {}\n\n\
+                To proceed, call this tool again with action: \"confirm\"",
+                effect,
+                email.subject.as_deref().unwrap_or("(no subject)"),
+                format_address_list(email.from.as_ref())
+            ));
+        }
+
+        let result = if permanent {
+            client.destroy_email(&req.email_id).await
+        } else {
+            match client.find_mailbox("trash").await {
+                Ok(trash) => client.move_email(&req.email_id, &trash.id).await,
+                Err(e) => Err(e),
+            }
+        };
+
+        match result {
+            Ok(()) => Self::text_result(format!(
+                "{}: \"{}\" from {}",
+                if permanent {
+                    "Permanently deleted"
+                } else {
+                    "Moved to Trash"
+                },
+                email.subject.as_deref().unwrap_or("(no subject)"),
+                format_address_list(email.from.as_ref())
+            )),
+            Err(e) => Self::error_result(format!("Failed to delete email: {}", e)),
+        }
+    }
+
+    #[tool(
+        description = "Empty a mailbox (e.g. Trash or Junk): permanently deletes emails only in it and removes emails that are also in other mailboxes from it. Cannot be undone! MUST use action='preview' first, then 'confirm' after user approval."
+    )]
+    async fn empty_mailbox(&self, Parameters(req): Parameters<EmptyMailboxRequest>) -> ToolResult {
+        let client = self.client.lock().await;
+
+        let mailbox = match client.find_mailbox(&req.mailbox).await {
+            Ok(m) => m,
+            Err(e) => {
+                return Self::error_result(format!("Mailbox not found: {} ({})", req.mailbox, e));
+            }
+        };
+
+        let plan = match client.plan_empty_mailbox(&mailbox.id).await {
+            Ok(plan) => plan,
+            Err(e) => return Self::error_result(format!("Failed to list emails: {}", e)),
+        };
+
+        if req.action == "preview" {
+            return Self::text_result(format!(
+                "EMPTY MAILBOX PREVIEW - This will:\n\
+                Permanently delete {} email(s) that are only in {} - this CANNOT be undone\n\
+                Remove {} email(s) from {} that are also in other mailboxes (kept there)\n\n\
+                To proceed, call this tool again with action: \"confirm\"",
+                plan.destroy.len(),
+                mailbox.name,
+                plan.remove.len(),
+                mailbox.name
+            ));
+        }
+
+        let total = plan.destroy.len() + plan.remove.len();
+        match client.empty_mailbox(&mailbox.id, &plan).await {
+            Ok(result) if result.failed.is_empty() => Self::text_result(format!(
+                "Emptied {}: deleted {} email(s), removed {} that are also in other mailboxes",
+                mailbox.name,
+                plan.destroy.len(),
+                plan.remove.len()
+            )),
+            Ok(result) => Self::error_result(format!(
+                "Emptied {} partially: {} of {} email(s) succeeded ({} to delete, {} to remove)",
+                mailbox.name,
+                result.succeeded.len(),
+                total,
+                plan.destroy.len(),
+                plan.remove.len()
+            )),
+            Err(e) => Self::error_result(format!("Failed to empty mailbox: {}", e)),
+        }
+    }
+
     // ============ Send/Reply/Forward Tools ============
 
     #[tool(
@@ -517,18 +778,42 @@ impl FastmailMcp {
             .as_ref()
             .map(|s| Self::parse_addresses(s))
             .unwrap_or_default();
+        let attachments = req.attachments.unwrap_or_default();
+        let (body, html) = match Self::render_body(&req.body, req.body_format.as_deref()).await {
+            Ok(rendered) => rendered,
+            Err(e) => return Self::error_result(e),
+        };
+
+        if let Some(missing) = attachments
+            .iter()
+            .find(|p| !std::path::Path::new(p).is_file())
+        {
+            return Self::error_result(format!("Attachment not found: {}", missing));
+        }
+
+        let client = self.client.lock().await;
+        let identity = match client.find_identity(req.from.as_deref()).await {
+            Ok(identity) => identity,
+            Err(e) => return Self::error_result(e.to_string()),
+        };
+        let signature = req.signature.unwrap_or(false);
 
         if req.action == "preview" {
             return Self::text_result(format!(
                 "EMAIL PREVIEW - Review before sending:\n\n\
+                From: {}\n\
                 To: {}\n\
                 CC: {}\n\
                 BCC: {}\n\
-                Subject: {}\n\n\
+                Subject: {}\n\
+                Attachments: {}\n\
+                Format: {}\n\
+                Signature: {}\n\n\
                 --- Body ---\n\
                 {}\n\n\
                 ---\n\
                 To send this email, call this tool again with action: \"confirm\" and the same parameters.",
+                format_identity_address(&identity),
                 format_address_list(Some(&to_addrs)),
                 if cc_addrs.is_empty() {
                     "(none)".to_string()
@@ -541,19 +826,37 @@ impl FastmailMcp {
                     format_address_list(Some(&bcc_addrs))
                 },
                 req.subject,
+                if attachments.is_empty() {
+                    "(none)".to_string()
+                } else {
+                    attachments.join(", ")
+                },
+                req.body_format.as_deref().unwrap_or("text"),
+                format_signature_preview(&identity, signature),
                 req.body
             ));
         }
 
-        let client = self.client.lock().await;
+        let options = match client.upload_attachments(&attachments).await {
+            Ok(parts) => ComposeOptions {
+                attachments: parts,
+                html,
+                identity: Some(identity),
+                signature,
+                ..Default::default()
+            },
+            Err(e) => return Self::error_result(format!("Failed to upload attachments: {}", e)),
+        };
+
         match client
             .send_email(
                 to_addrs.clone(),
                 cc_addrs,
                 bcc_addrs,
                 &req.subject,
-                &req.body,
+                &body,
                 None,
+                &options,
             )
             .await
         {
@@ -606,19 +909,32 @@ impl FastmailMcp {
 
         // Determine recipients
         let to_addrs: Vec<EmailAddress> = original.from.clone().unwrap_or_default();
+        let (body, html) = match Self::render_body(&req.body, req.body_format.as_deref()).await {
+            Ok(rendered) => rendered,
+            Err(e) => return Self::error_result(e),
+        };
+
+        let identity = match client.reply_identity(&original, req.from.as_deref()).await {
+            Ok(identity) => identity,
+            Err(e) => return Self::error_result(e.to_string()),
+        };
+        let signature = req.signature.unwrap_or(false);
 
         if req.action == "preview" {
             return Self::text_result(format!(
                 "REPLY PREVIEW - Review before sending:\n\n\
+                From: {}\n\
                 To: {}\n\
                 CC: {}\n\
                 BCC: {}\n\
                 Subject: {}\n\
-                In-Reply-To: {}\n\n\
+                In-Reply-To: {}\n\
+                Signature: {}\n\n\
                 --- Your Reply ---\n\
                 {}\n\n\
                 ---\n\
                 To send this reply, call this tool again with action: \"confirm\" and the same parameters.",
+                format_identity_address(&identity),
                 format_address_list(Some(&to_addrs)),
                 if cc_addrs.is_empty() {
                     "(none)".to_string()
@@ -636,12 +952,25 @@ impl FastmailMcp {
                     .as_ref()
                     .and_then(|v| v.first())
                     .unwrap_or(&"(none)".to_string()),
+                format_signature_preview(&identity, signature),
                 req.body
             ));
         }
 
         match client
-            .reply_email(&original, &req.body, reply_all, cc_addrs, bcc_addrs)
+            .reply_email(
+                &original,
+                &body,
+                reply_all,
+                cc_addrs,
+                bcc_addrs,
+                &ComposeOptions {
+                    html,
+                    identity: Some(identity),
+                    signature,
+                    ..Default::default()
+                },
+            )
             .await
         {
             Ok(email_id) => Self::text_result(format!(
@@ -679,7 +1008,15 @@ impl FastmailMcp {
             .as_ref()
             .map(|s| Self::parse_addresses(s))
             .unwrap_or_default();
-        let body = req.body.as_deref().unwrap_or("");
+        let (body, html) = match Self::render_body(
+            req.body.as_deref().unwrap_or(""),
+            req.body_format.as_deref(),
+        )
+        .await
+        {
+            Ok(rendered) => rendered,
+            Err(e) => return Self::error_result(e),
+        };
 
         // Build subject
         let subject = if original
@@ -701,24 +1038,60 @@ impl FastmailMcp {
             .unwrap_or("");
 
         let sender = format_address_list(original.from.as_ref());
+        let mode = if req.as_attachment.unwrap_or(false) {
+            ForwardMode::Attachment
+        } else {
+            ForwardMode::Inline
+        };
+
+        let identity = match client.find_identity(req.from.as_deref()).await {
+            Ok(identity) => identity,
+            Err(e) => return Self::error_result(e.to_string()),
+        };
+        let signature = req.signature.unwrap_or(false);
 
         if req.action == "preview" {
+            let forwarded = match mode {
+                ForwardMode::Inline => format!(
+                    "---------- Forwarded message ---------\n\
+                    From: {}\n\
+                    Date: {}\n\
+                    Subject: {}\n\n\
+                    {}\n\n\
+                    Attachments carried over: {}",
+                    sender,
+                    original.received_at.as_deref().unwrap_or("unknown date"),
+                    original.subject.as_deref().unwrap_or(""),
+                    original_body,
+                    original
+                        .attachments
+                        .as_ref()
+                        .filter(|a| !a.is_empty())
+                        .map(|a| a
+                            .iter()
+                            .map(|p| p.name.as_deref().unwrap_or("(unnamed)"))
+                            .collect::<Vec<_>>()
+                            .join(", "))
+                        .unwrap_or_else(|| "(none)".to_string())
+                ),
+                ForwardMode::Attachment => "[Original message attached as .eml file]".to_string(),
+            };
+
             return Self::text_result(format!(
                 "FORWARD PREVIEW - Review before sending:\n\n\
+                From: {}\n\
                 To: {}\n\
                 CC: {}\n\
                 BCC: {}\n\
                 Subject: {}\n\
-                Forwarding from: {}\n\n\
+                Forwarding from: {}\n\
+                Signature: {}\n\n\
                 --- Your Message + Forwarded Content ---\n\
                 {}\n\n\
-                ---------- Forwarded message ---------\n\
-                From: {}\n\
-                Date: {}\n\
-                Subject: {}\n\n\
                 {}\n\n\
                 ---\n\
                 To send this forward, call this tool again with action: \"confirm\" and the same parameters.",
+                format_identity_address(&identity),
                 format_address_list(Some(&to_addrs)),
                 if cc_addrs.is_empty() {
                     "(none)".to_string()
@@ -732,16 +1105,27 @@ impl FastmailMcp {
                 },
                 subject,
                 sender,
+                format_signature_preview(&identity, signature),
                 body,
-                sender,
-                original.received_at.as_deref().unwrap_or("unknown date"),
-                original.subject.as_deref().unwrap_or(""),
-                original_body
+                forwarded
             ));
         }
 
         match client
-            .forward_email(&original, to_addrs.clone(), body, cc_addrs, bcc_addrs)
+            .forward_email(
+                &original,
+                to_addrs.clone(),
+                &body,
+                cc_addrs,
+                bcc_addrs,
+                mode,
+                &ComposeOptions {
+                    html,
+                    identity: Some(identity),
+                    signature,
+                    ..Default::default()
+                },
+            )
             .await
         {
             Ok(email_id) => Self::text_result(format!(
@@ -757,6 +1141,71 @@ impl FastmailMcp {
         }
     }
 
+    #[tool(
+        description = "Save an email to the Drafts folder without sending it, so the user can review and send it from Fastmail. Use this instead of send_email when the user wants to check or finish the message themselves."
+    )]
+    async fn save_draft(&self, Parameters(req): Parameters<SaveDraftRequest>) -> ToolResult {
+        let to_addrs = Self::parse_addresses(&req.to);
+        let cc_addrs = req
+            .cc
+            .as_ref()
+            .map(|s| Self::parse_addresses(s))
+            .unwrap_or_default();
+        let bcc_addrs = req
+            .bcc
+            .as_ref()
+            .map(|s| Self::parse_addresses(s))
+            .unwrap_or_default();
+        let attachments = req.attachments.unwrap_or_default();
+        let (body, html) = match Self::render_body(&req.body, req.body_format.as_deref()).await {
+            Ok(rendered) => rendered,
+            Err(e) => return Self::error_result(e),
+        };
+
+        if let Some(missing) = attachments
+            .iter()
+            .find(|p| !std::path::Path::new(p).is_file())
+        {
+            return Self::error_result(format!("Attachment not found: {}", missing));
+        }
+
+        let client = self.client.lock().await;
+        let options = match client.upload_attachments(&attachments).await {
+            Ok(parts) => ComposeOptions {
+                attachments: parts,
+                html,
+                from: req.from,
+                signature: req.signature.unwrap_or(false),
+                ..Default::default()
+            },
+            Err(e) => return Self::error_result(format!("Failed to upload attachments: {}", e)),
+        };
+
+        match client
+            .save_draft(
+                to_addrs.clone(),
+                cc_addrs,
+                bcc_addrs,
+                &req.subject,
+                &body,
+                &options,
+                None,
+            )
+            .await
+        {
+            Ok(email_id) => Self::text_result(format!(
+                "Draft saved to the Drafts folder (not sent).\n\
+                To: {}\n\
+                Subject: {}\n\
+                Draft ID: {}",
+                format_address_list(Some(&to_addrs)),
+                req.subject,
+                email_id
+            )),
+            Err(e) => Self::error_result(format!("Failed to save draft: {}", e)),
+        }
+    }
+
     // ============ Attachment Tools ============
 
     #[tool(
@@ -1009,10 +1458,7 @@ impl FastmailMcp {
         &self,
         Parameters(req): Parameters<SearchContactsRequest>,
     ) -> ToolResult {
-        let config = match Config::load() {
-            Ok(c) => c,
-            Err(e) => return Self::error_result(format!("Config error: {}", e)),
-        };
+        let config = &self.config;
 
         let username = match config.get_username() {
             Ok(u) => u,
@@ -1032,7 +1478,8 @@ impl FastmailMcp {
             }
         };
 
-        let client = CardDavClient::new(username, app_password);
+        let client =
+            CardDavClient::new(username, app_password).with_base_url(config.get_carddav_url());
 
         match client.search_contacts(&req.query).await {
             Ok(contacts) => {
@@ -1079,11 +1526,14 @@ impl ServerHandler for FastmailMcp {
                 ## Sending Emails (ALWAYS preview first!)\n\
                 1. Use `send_email` with action=\"preview\" to draft\n\
                 2. Review the preview with the user\n\
-                3. Only use action=\"confirm\" after explicit user approval\n\n\
+                3. Only use action=\"confirm\" after explicit user approval\n\
+                4. Use `list_identities` to pick a `from` address when the user has aliases\n\
+                5. Use `save_draft` when the user wants to review and send from Fastmail themselves\n\n\
                 ## Safety Rules\n\
                 - NEVER send without showing preview first\n\
                 - NEVER confirm send without explicit user approval\n\
-                - Be careful with mark_as_spam - it affects future filtering"
+                - Be careful with mark_as_spam - it affects future filtering\n\
+                - delete_email with permanent=true and empty_mailbox cannot be undone"
                     .to_string(),
             ),
         }
@@ -1091,10 +1541,10 @@ impl ServerHandler for FastmailMcp {
 }
 
 /// Run the MCP server with stdio transport
-pub async fn run_server() -> anyhow::Result<()> {
+pub async fn run_server(config: Config) -> anyhow::Result<()> {
     use rmcp::{ServiceExt, transport::stdio};
 
-    let service = FastmailMcp::new().await?;
+    let service = FastmailMcp::new(config).await?;
     let server = service
         .serve(stdio())
         .await
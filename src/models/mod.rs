@@ -1,3 +1,4 @@
+use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -18,11 +19,77 @@ pub struct Session {
 }
 
 impl Session {
+    pub fn has_capability(&self, capability: &str) -> bool {
+        self.capabilities.contains_key(capability)
+    }
+
+    /// Server limits, with defaults for anything missing or malformed
+    pub fn core_capabilities(&self) -> CoreCapabilities {
+        self.capabilities
+            .get("urn:ietf:params:jmap:core")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default()
+    }
+
     pub fn primary_account_id(&self) -> Option<&str> {
         self.primary_accounts
             .get("urn:ietf:params:jmap:mail")
             .map(String::as_str)
     }
+
+    /// Resolve an account by ID, or by name (case-insensitive), to its ID
+    pub fn find_account(&self, name_or_id: &str) -> Option<&str> {
+        if let Some((id, _)) = self.accounts.get_key_value(name_or_id) {
+            return Some(id);
+        }
+        self.accounts
+            .iter()
+            .find(|(_, a)| a.name.eq_ignore_ascii_case(name_or_id))
+            .map(|(id, _)| id.as_str())
+    }
+
+    /// Longest delay (in seconds) the server accepts for a scheduled send; 0 means unsupported
+    pub fn max_delayed_send(&self, account_id: &str) -> u64 {
+        self.accounts
+            .get(account_id)
+            .and_then(|a| {
+                a.account_capabilities
+                    .get("urn:ietf:params:jmap:submission")
+            })
+            .and_then(|c| c.get("maxDelayedSend"))
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0)
+    }
+}
+
+/// Limits from the `urn:ietf:params:jmap:core` session capability (RFC 8620 §2)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CoreCapabilities {
+    pub max_size_upload: u64,
+    pub max_concurrent_upload: u64,
+    pub max_size_request: u64,
+    pub max_concurrent_requests: u64,
+    pub max_calls_in_request: u64,
+    pub max_objects_in_get: u64,
+    pub max_objects_in_set: u64,
+    pub collation_algorithms: Vec<String>,
+}
+
+impl Default for CoreCapabilities {
+    /// Conservative values for servers that don't advertise a limit
+    fn default() -> Self {
+        Self {
+            max_size_upload: 50_000_000,
+            max_concurrent_upload: 4,
+            max_size_request: 10_000_000,
+            max_concurrent_requests: 4,
+            max_calls_in_request: 16,
+            max_objects_in_get: 500,
+            max_objects_in_set: 500,
+            collation_algorithms: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -70,6 +137,154 @@ pub struct Mailbox {
     pub unread_threads: u32,
     #[serde(default)]
     pub sort_order: u32,
+    #[serde(default)]
+    pub is_subscribed: bool,
+    /// What the user may do in this mailbox; absent if the server didn't say
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub my_rights: Option<MailboxRights>,
+    /// Rights granted to other users, keyed by principal ID (JMAP Sharing)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub share_with: Option<HashMap<String, MailboxRights>>,
+    /// Whether the `$seen` keyword is shared between everyone with access
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_seen_shared: Option<bool>,
+}
+
+/// Mailbox access rights (RFC 8621 `MailboxRights`)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MailboxRights {
+    pub may_read_items: bool,
+    pub may_add_items: bool,
+    pub may_remove_items: bool,
+    pub may_set_seen: bool,
+    pub may_set_keywords: bool,
+    pub may_create_child: bool,
+    pub may_rename: bool,
+    pub may_delete: bool,
+    pub may_submit: bool,
+    /// JMAP Sharing: may change `shareWith`
+    pub may_admin: bool,
+}
+
+impl Mailbox {
+    /// False when the server says emails can't be filed here (e.g. a read-only shared folder)
+    pub fn may_add_items(&self) -> bool {
+        self.my_rights.as_ref().is_none_or(|r| r.may_add_items)
+    }
+
+    /// Slash-separated path from the top level, e.g. `Clients/Acme/2024`
+    pub fn path(&self, mailboxes: &[Mailbox]) -> String {
+        let mut segments = vec![self.name.as_str()];
+        let mut parent = self.parent_id.as_deref();
+        // Bounded by the number of mailboxes in case the server sends a cycle
+        while let Some(id) = parent {
+            if segments.len() > mailboxes.len() {
+                break;
+            }
+            match mailboxes.iter().find(|m| m.id == id) {
+                Some(m) => {
+                    segments.push(&m.name);
+                    parent = m.parent_id.as_deref();
+                }
+                None => break,
+            }
+        }
+        segments.reverse();
+        segments.join("/")
+    }
+
+    /// Find a mailbox by full path, then role, then trailing path segments
+    /// (so `2024` or `Acme/2024` find `Clients/Acme/2024`). Case-insensitive;
+    /// a name that matches several mailboxes is an error listing their paths.
+    pub fn resolve<'a>(mailboxes: &'a [Mailbox], name: &str) -> Result<&'a Mailbox> {
+        let wanted = name.trim_matches('/').to_lowercase();
+        let paths: Vec<String> = mailboxes
+            .iter()
+            .map(|m| m.path(mailboxes).to_lowercase())
+            .collect();
+
+        if let Some(i) = paths.iter().position(|p| *p == wanted) {
+            return Ok(&mailboxes[i]);
+        }
+
+        if let Some(m) = mailboxes
+            .iter()
+            .find(|m| m.role.as_deref().map(str::to_lowercase).as_deref() == Some(wanted.as_str()))
+        {
+            return Ok(m);
+        }
+
+        let suffix = format!("/{}", wanted);
+        let matches: Vec<&Mailbox> = mailboxes
+            .iter()
+            .zip(&paths)
+            .filter(|(_, p)| p.ends_with(&suffix))
+            .map(|(m, _)| m)
+            .collect();
+
+        match matches.as_slice() {
+            [] => Err(Error::MailboxNotFound(name.into())),
+            [m] => Ok(m),
+            _ => Err(Error::AmbiguousMailbox(
+                name.into(),
+                matches.iter().map(|m| m.path(mailboxes)).collect(),
+            )),
+        }
+    }
+}
+
+/// A mailbox with its path and children, for `list mailboxes --tree`
+#[derive(Debug, Clone, Serialize)]
+pub struct MailboxNode {
+    #[serde(flatten)]
+    pub mailbox: Mailbox,
+    pub path: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<MailboxNode>,
+}
+
+impl MailboxNode {
+    /// Arrange mailboxes by `parent_id`, siblings ordered by `sort_order` then name.
+    /// Mailboxes whose parent is missing are treated as top-level.
+    pub fn tree(mailboxes: &[Mailbox]) -> Vec<MailboxNode> {
+        let is_root = |m: &Mailbox| match m.parent_id.as_deref() {
+            Some(parent) => !mailboxes.iter().any(|p| p.id == parent),
+            None => true,
+        };
+        Self::children(
+            mailboxes,
+            mailboxes.iter().filter(|m| is_root(m)).collect(),
+            0,
+        )
+    }
+
+    fn children(mailboxes: &[Mailbox], mut level: Vec<&Mailbox>, depth: usize) -> Vec<MailboxNode> {
+        level.sort_by(|a, b| {
+            a.sort_order
+                .cmp(&b.sort_order)
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        level
+            .into_iter()
+            .map(|m| {
+                let children = if depth < mailboxes.len() {
+                    let kids = mailboxes
+                        .iter()
+                        .filter(|c| c.parent_id.as_deref() == Some(m.id.as_str()))
+                        .collect();
+                    Self::children(mailboxes, kids, depth + 1)
+                } else {
+                    Vec::new()
+                };
+                MailboxNode {
+                    mailbox: m.clone(),
+                    path: m.path(mailboxes),
+                    children,
+                }
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -191,6 +406,92 @@ impl Email {
     }
 }
 
+/// One page of Email/query results
+#[derive(Debug, Clone, Serialize)]
+pub struct EmailPage {
+    pub emails: Vec<Email>,
+    /// Zero-based index of the first email in the full result list
+    pub position: u64,
+    pub total: Option<u64>,
+    /// ID of the last email, when more results follow
+    pub next_page_token: Option<String>,
+}
+
+/// Per-email outcome of a bulk Email/set
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BulkResult {
+    pub succeeded: Vec<String>,
+    pub failed: Vec<BulkFailure>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkFailure {
+    pub id: String,
+    pub error_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl BulkResult {
+    /// Record a batch from an Email/set `updated`/`destroyed` list and the matching
+    /// `notUpdated`/`notDestroyed` map. IDs in neither are reported as failed.
+    pub fn record<'a>(
+        &mut self,
+        ids: &[String],
+        done: impl IntoIterator<Item = &'a String>,
+        errors: Option<&HashMap<String, serde_json::Value>>,
+    ) {
+        let done: Vec<&String> = done.into_iter().collect();
+        for id in ids {
+            if done.contains(&id) {
+                self.succeeded.push(id.clone());
+                continue;
+            }
+            let err = errors.and_then(|e| e.get(id));
+            self.failed.push(BulkFailure {
+                id: id.clone(),
+                error_type: err
+                    .and_then(|e| e.get("type"))
+                    .and_then(|t| t.as_str())
+                    .unwrap_or("unknown")
+                    .to_string(),
+                description: err
+                    .and_then(|e| e.get("description"))
+                    .and_then(|d| d.as_str())
+                    .map(String::from),
+            });
+        }
+    }
+}
+
+/// How to empty a mailbox without losing mail that is also filed elsewhere:
+/// `Email/set destroy` removes an email from every mailbox, not just this one
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EmptyPlan {
+    /// Emails in this mailbox only, to destroy
+    pub destroy: Vec<String>,
+    /// Emails also in other mailboxes, to remove from this one only
+    pub remove: Vec<String>,
+}
+
+impl EmptyPlan {
+    pub fn new(emails: &[Email], mailbox_id: &str) -> Self {
+        let mut plan = Self::default();
+        for email in emails {
+            let elsewhere = email
+                .mailbox_ids
+                .iter()
+                .any(|(id, &member)| member && id != mailbox_id);
+            if elsewhere {
+                plan.remove.push(email.id.clone());
+            } else {
+                plan.destroy.push(email.id.clone());
+            }
+        }
+        plan
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Identity {
@@ -209,6 +510,164 @@ pub struct Identity {
     pub may_delete: bool,
 }
 
+impl Identity {
+    /// Fastmail wildcard identities look like `*@example.com`
+    pub fn is_wildcard(&self) -> bool {
+        self.email.starts_with("*@")
+    }
+
+    /// Whether this identity can send as the given address
+    pub fn matches(&self, address: &str) -> bool {
+        let address = address.to_lowercase();
+        let email = self.email.to_lowercase();
+        match email.strip_prefix('*') {
+            Some(domain) => address.ends_with(domain),
+            None => address == email,
+        }
+    }
+
+    /// Copy of this identity sending as `address` (only differs for wildcard identities)
+    pub fn for_address(&self, address: &str) -> Identity {
+        let mut identity = self.clone();
+        if self.is_wildcard() {
+            identity.email = address.to_string();
+        }
+        identity
+    }
+
+    /// The identity `from` names: an identity ID, or an address one can send as. A wildcard
+    /// identity can't be chosen by ID alone, as that leaves no address to send from.
+    pub fn find(identities: &[Identity], from: &str) -> Result<Identity> {
+        if let Some(identity) = identities.iter().find(|i| i.id == from) {
+            if identity.is_wildcard() {
+                return Err(Error::WildcardIdentity(
+                    from.to_string(),
+                    identity.email.clone(),
+                ));
+            }
+            return Ok(identity.clone());
+        }
+        let address = EmailAddress {
+            name: None,
+            email: from.to_string(),
+        };
+        Identity::select(identities, [&address]).ok_or_else(|| Error::UnknownIdentity(from.into()))
+    }
+
+    /// Find the identity for any of the given addresses, preferring exact over wildcard matches
+    pub fn select<'a>(
+        identities: &[Identity],
+        addresses: impl IntoIterator<Item = &'a EmailAddress>,
+    ) -> Option<Identity> {
+        let addresses: Vec<&EmailAddress> = addresses.into_iter().collect();
+        let find = |wildcard: bool| {
+            addresses.iter().find_map(|addr| {
+                identities
+                    .iter()
+                    .filter(|i| i.is_wildcard() == wildcard)
+                    .find(|i| i.matches(&addr.email))
+                    .map(|i| i.for_address(&addr.email))
+            })
+        };
+        find(false).or_else(|| find(true))
+    }
+}
+
+/// SMTP envelope address with optional extension parameters (e.g. HOLDUNTIL)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmissionAddress {
+    pub email: String,
+    #[serde(default)]
+    pub parameters: Option<HashMap<String, Option<String>>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope {
+    pub mail_from: SubmissionAddress,
+    pub rcpt_to: Vec<SubmissionAddress>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailSubmission {
+    pub id: String,
+    pub identity_id: String,
+    pub email_id: String,
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    #[serde(default)]
+    pub envelope: Option<Envelope>,
+    #[serde(default)]
+    pub send_at: Option<String>,
+    /// "pending", "final" or "canceled"
+    pub undo_status: String,
+}
+
+/// A push notification (RFC 8620 section 7.1): new state strings by account ID, then type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateChange {
+    #[serde(rename = "@type")]
+    pub type_name: String,
+    pub changed: HashMap<String, HashMap<String, String>>,
+}
+
+/// Response to `Email/changes`, `Mailbox/changes` or `Thread/changes` (RFC 8620 section 5.2)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Changes {
+    pub old_state: String,
+    pub new_state: String,
+    /// More changes remain after `new_state`; call again from there
+    #[serde(default, skip_serializing)]
+    pub has_more_changes: bool,
+    #[serde(default)]
+    pub created: Vec<String>,
+    #[serde(default)]
+    pub updated: Vec<String>,
+    #[serde(default)]
+    pub destroyed: Vec<String>,
+}
+
+impl Changes {
+    /// No changes: `state` is both the old and new state
+    pub fn none(state: String) -> Self {
+        Self {
+            old_state: state.clone(),
+            new_state: state,
+            has_more_changes: false,
+            created: Vec::new(),
+            updated: Vec::new(),
+            destroyed: Vec::new(),
+        }
+    }
+
+    /// Fold in the next page of changes so each ID appears once, as of the final state:
+    /// created then updated stays created, created then destroyed disappears
+    pub fn merge(&mut self, next: Changes) {
+        for id in next.created {
+            if !self.created.contains(&id) {
+                self.created.push(id);
+            }
+        }
+        for id in next.updated {
+            if !self.created.contains(&id) && !self.updated.contains(&id) {
+                self.updated.push(id);
+            }
+        }
+        for id in next.destroyed {
+            self.updated.retain(|u| *u != id);
+            if let Some(pos) = self.created.iter().position(|c| *c == id) {
+                self.created.remove(pos);
+            } else if !self.destroyed.contains(&id) {
+                self.destroyed.push(id);
+            }
+        }
+        self.new_state = next.new_state;
+        self.has_more_changes = next.has_more_changes;
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MaskedEmail {
@@ -236,6 +695,12 @@ pub struct Output<T: Serialize> {
     pub success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<T>,
+    /// Total matching results, for paged queries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    /// Pass to --page-token to fetch the next page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_page_token: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -249,6 +714,8 @@ impl<T: Serialize> Output<T> {
             data: Some(data),
             error: None,
             message: None,
+            total: None,
+            next_page_token: None,
         }
     }
 
@@ -258,6 +725,8 @@ impl<T: Serialize> Output<T> {
             data: None,
             error: None,
             message: Some(message.into()),
+            total: None,
+            next_page_token: None,
         }
     }
 
@@ -267,14 +736,33 @@ impl<T: Serialize> Output<T> {
             data: None,
             error: Some(err.into()),
             message: None,
+            total: None,
+            next_page_token: None,
         }
     }
 
+    /// Attach paging information from a query
+    pub fn paged(mut self, total: Option<u64>, next_page_token: Option<String>) -> Self {
+        self.total = total;
+        self.next_page_token = next_page_token;
+        self
+    }
+
     pub fn print(&self) {
         println!("{}", serde_json::to_string_pretty(self).unwrap());
     }
 }
 
+/// Print one compact JSON value per line and flush, for streamed output (`--all`, `watch`).
+/// Errors (such as a closed pipe) are returned so the caller can stop.
+pub fn print_json_line(value: &impl Serialize) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer(&mut stdout, value)?;
+    writeln!(stdout)?;
+    stdout.flush()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -433,6 +921,193 @@ mod tests {
         assert_eq!(email.sender_display(), "(unknown)");
     }
 
+    fn identity(id: &str, email: &str) -> Identity {
+        Identity {
+            id: id.to_string(),
+            name: "Me".to_string(),
+            email: email.to_string(),
+            reply_to: None,
+            bcc: None,
+            text_signature: None,
+            html_signature: None,
+            may_delete: true,
+        }
+    }
+
+    fn address(email: &str) -> EmailAddress {
+        EmailAddress {
+            name: None,
+            email: email.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_identity_matches() {
+        assert!(identity("i1", "me@example.com").matches("ME@example.com"));
+        assert!(!identity("i1", "me@example.com").matches("you@example.com"));
+        assert!(identity("i2", "*@example.com").matches("anything@example.com"));
+        assert!(!identity("i2", "*@example.com").matches("me@other.com"));
+    }
+
+    #[test]
+    fn test_identity_select_prefers_exact_match() {
+        let identities = vec![
+            identity("wild", "*@example.com"),
+            identity("alias", "alias@example.com"),
+        ];
+        let addrs = [address("alias@example.com")];
+        let selected = Identity::select(&identities, &addrs).unwrap();
+        assert_eq!(selected.id, "alias");
+    }
+
+    #[test]
+    fn test_identity_select_wildcard_uses_recipient_address() {
+        let identities = vec![
+            identity("main", "me@fastmail.com"),
+            identity("wild", "*@example.com"),
+        ];
+        let addrs = [address("someone@else.com"), address("shop@example.com")];
+        let selected = Identity::select(&identities, &addrs).unwrap();
+        assert_eq!(selected.id, "wild");
+        assert_eq!(selected.email, "shop@example.com");
+    }
+
+    #[test]
+    fn test_identity_find_wildcard() {
+        let identities = vec![
+            identity("main", "me@fastmail.com"),
+            identity("wild", "*@example.com"),
+        ];
+        assert_eq!(
+            Identity::find(&identities, "main").unwrap().email,
+            "me@fastmail.com"
+        );
+
+        let shop = Identity::find(&identities, "shop@example.com").unwrap();
+        assert_eq!(shop.id, "wild");
+        assert_eq!(shop.email, "shop@example.com");
+
+        // The ID alone doesn't say which address to send as
+        assert!(matches!(
+            Identity::find(&identities, "wild"),
+            Err(Error::WildcardIdentity(..))
+        ));
+        assert!(matches!(
+            Identity::find(&identities, "me@other.com"),
+            Err(Error::UnknownIdentity(_))
+        ));
+    }
+
+    #[test]
+    fn test_identity_select_none() {
+        let identities = vec![identity("main", "me@fastmail.com")];
+        assert!(Identity::select(&identities, &[address("x@y.com")]).is_none());
+    }
+
+    fn mailbox(id: &str, name: &str, parent: Option<&str>, role: Option<&str>) -> Mailbox {
+        Mailbox {
+            id: id.to_string(),
+            name: name.to_string(),
+            parent_id: parent.map(String::from),
+            role: role.map(String::from),
+            total_emails: 0,
+            unread_emails: 0,
+            total_threads: 0,
+            unread_threads: 0,
+            sort_order: 0,
+            is_subscribed: true,
+            my_rights: None,
+            share_with: None,
+            is_seen_shared: None,
+        }
+    }
+
+    fn mailboxes() -> Vec<Mailbox> {
+        vec![
+            mailbox("inbox", "Inbox", None, Some("inbox")),
+            mailbox("trash", "Deleted Items", None, Some("trash")),
+            mailbox("clients", "Clients", None, None),
+            mailbox("acme", "Acme", Some("clients"), None),
+            mailbox("acme-2024", "2024", Some("acme"), None),
+            mailbox("globex", "Globex", Some("clients"), None),
+            mailbox("globex-2024", "2024", Some("globex"), None),
+            mailbox("projects-trash", "Trash", Some("clients"), None),
+        ]
+    }
+
+    #[test]
+    fn test_mailbox_path() {
+        let all = mailboxes();
+        assert_eq!(all[4].path(&all), "Clients/Acme/2024");
+        assert_eq!(all[0].path(&all), "Inbox");
+    }
+
+    #[test]
+    fn test_mailbox_resolve_path() {
+        let all = mailboxes();
+        let m = Mailbox::resolve(&all, "clients/acme/2024").unwrap();
+        assert_eq!(m.id, "acme-2024");
+        let m = Mailbox::resolve(&all, "Globex/2024").unwrap();
+        assert_eq!(m.id, "globex-2024");
+        let m = Mailbox::resolve(&all, "Acme").unwrap();
+        assert_eq!(m.id, "acme");
+    }
+
+    #[test]
+    fn test_mailbox_resolve_role_before_leaf_name() {
+        let all = mailboxes();
+        assert_eq!(Mailbox::resolve(&all, "trash").unwrap().id, "trash");
+        assert_eq!(Mailbox::resolve(&all, "INBOX").unwrap().id, "inbox");
+    }
+
+    #[test]
+    fn test_mailbox_resolve_ambiguous() {
+        let all = mailboxes();
+        match Mailbox::resolve(&all, "2024") {
+            Err(Error::AmbiguousMailbox(_, candidates)) => {
+                assert_eq!(candidates, vec!["Clients/Acme/2024", "Clients/Globex/2024"]);
+            }
+            other => panic!("expected ambiguity, got {:?}", other),
+        }
+        assert!(matches!(
+            Mailbox::resolve(&all, "Nope"),
+            Err(Error::MailboxNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_mailbox_rights_deserialize() {
+        let m: Mailbox = serde_json::from_value(serde_json::json!({
+            "id": "team",
+            "name": "Team",
+            "isSubscribed": true,
+            "myRights": { "mayReadItems": true, "mayAddItems": false },
+            "shareWith": { "p1": { "mayReadItems": true, "mayAdmin": true } }
+        }))
+        .unwrap();
+        assert!(m.is_subscribed);
+        assert!(!m.may_add_items());
+        assert!(m.share_with.unwrap()["p1"].may_admin);
+        assert!(mailbox("x", "X", None, None).may_add_items());
+    }
+
+    #[test]
+    fn test_mailbox_tree() {
+        let mut all = mailboxes();
+        all[7].sort_order = 10;
+        let tree = MailboxNode::tree(&all);
+        let roots: Vec<&str> = tree.iter().map(|n| n.mailbox.name.as_str()).collect();
+        assert_eq!(roots, vec!["Clients", "Deleted Items", "Inbox"]);
+
+        let clients = &tree[0];
+        let children: Vec<&str> = clients.children.iter().map(|n| n.path.as_str()).collect();
+        assert_eq!(
+            children,
+            vec!["Clients/Acme", "Clients/Globex", "Clients/Trash"]
+        );
+        assert_eq!(clients.children[0].children[0].path, "Clients/Acme/2024");
+    }
+
     #[test]
     fn test_output_success() {
         let output: Output<&str> = Output::success("test data");
@@ -449,6 +1124,40 @@ mod tests {
         assert_eq!(output.error, Some("something broke".to_string()));
     }
 
+    #[test]
+    fn test_output_paged() {
+        let output = Output::success(vec![1, 2]).paged(Some(10), Some("M2".to_string()));
+        let json = serde_json::to_value(&output).unwrap();
+        assert_eq!(json["total"], 10);
+        assert_eq!(json["next_page_token"], "M2");
+
+        let json = serde_json::to_value(Output::success(vec![1])).unwrap();
+        assert!(json.get("total").is_none());
+        assert!(json.get("next_page_token").is_none());
+    }
+
+    #[test]
+    fn test_bulk_result_record() {
+        let ids: Vec<String> = ["M1", "M2", "M3"].iter().map(|s| s.to_string()).collect();
+        let updated: HashMap<String, serde_json::Value> =
+            HashMap::from([("M1".to_string(), serde_json::Value::Null)]);
+        let not_updated: HashMap<String, serde_json::Value> = HashMap::from([(
+            "M2".to_string(),
+            serde_json::json!({"type": "notFound", "description": "gone"}),
+        )]);
+
+        let mut result = BulkResult::default();
+        result.record(&ids, updated.keys(), Some(&not_updated));
+
+        assert_eq!(result.succeeded, vec!["M1"]);
+        assert_eq!(result.failed.len(), 2);
+        assert_eq!(result.failed[0].id, "M2");
+        assert_eq!(result.failed[0].error_type, "notFound");
+        assert_eq!(result.failed[0].description.as_deref(), Some("gone"));
+        assert_eq!(result.failed[1].id, "M3");
+        assert_eq!(result.failed[1].error_type, "unknown");
+    }
+
     #[test]
     fn test_session_deserialize() {
         let json = r#"{
@@ -465,6 +1174,104 @@ mod tests {
         assert_eq!(session.primary_account_id(), Some("acc1"));
     }
 
+    #[test]
+    fn test_session_max_delayed_send() {
+        let json = r#"{
+            "capabilities": {},
+            "accounts": {
+                "acc1": {
+                    "name": "test@example.com",
+                    "isPersonal": true,
+                    "isReadOnly": false,
+                    "accountCapabilities": {
+                        "urn:ietf:params:jmap:submission": {"maxDelayedSend": 2592000}
+                    }
+                }
+            },
+            "primaryAccounts": {"urn:ietf:params:jmap:mail": "acc1"},
+            "username": "test@example.com",
+            "apiUrl": "https://api.example.com/jmap",
+            "downloadUrl": "https://api.example.com/download",
+            "uploadUrl": "https://api.example.com/upload"
+        }"#;
+        let session: Session = serde_json::from_str(json).unwrap();
+        assert_eq!(session.max_delayed_send("acc1"), 2592000);
+        assert_eq!(session.max_delayed_send("other"), 0);
+    }
+
+    #[test]
+    fn test_session_core_capabilities() {
+        let json = r#"{
+            "capabilities": {
+                "urn:ietf:params:jmap:core": {
+                    "maxSizeUpload": 50000000,
+                    "maxCallsInRequest": 50,
+                    "maxObjectsInGet": 4096,
+                    "maxObjectsInSet": 4096,
+                    "maxSizeRequest": 10000000,
+                    "collationAlgorithms": ["i;ascii-numeric"]
+                }
+            },
+            "accounts": {},
+            "primaryAccounts": {},
+            "username": "me@example.com",
+            "apiUrl": "https://api.example.com/jmap",
+            "downloadUrl": "https://api.example.com/download",
+            "uploadUrl": "https://api.example.com/upload"
+        }"#;
+        let session: Session = serde_json::from_str(json).unwrap();
+        let core = session.core_capabilities();
+        assert_eq!(core.max_calls_in_request, 50);
+        assert_eq!(core.max_objects_in_get, 4096);
+        // Missing fields fall back to defaults
+        assert_eq!(core.max_concurrent_requests, 4);
+
+        let empty = Session {
+            capabilities: HashMap::new(),
+            ..session
+        };
+        assert_eq!(empty.core_capabilities().max_objects_in_set, 500);
+    }
+
+    #[test]
+    fn test_session_find_account() {
+        let json = r#"{
+            "capabilities": {},
+            "accounts": {
+                "acc1": {"name": "me@example.com", "isPersonal": true, "isReadOnly": false},
+                "acc2": {"name": "support@example.com", "isPersonal": false, "isReadOnly": false}
+            },
+            "primaryAccounts": {"urn:ietf:params:jmap:mail": "acc1"},
+            "username": "me@example.com",
+            "apiUrl": "https://api.example.com/jmap",
+            "downloadUrl": "https://api.example.com/download",
+            "uploadUrl": "https://api.example.com/upload"
+        }"#;
+        let session: Session = serde_json::from_str(json).unwrap();
+        assert_eq!(session.find_account("acc2"), Some("acc2"));
+        assert_eq!(session.find_account("Support@Example.com"), Some("acc2"));
+        assert_eq!(session.find_account("nobody@example.com"), None);
+    }
+
+    #[test]
+    fn test_email_submission_deserialize() {
+        let json = r#"{
+            "id": "S1",
+            "identityId": "I1",
+            "emailId": "M1",
+            "threadId": "T1",
+            "envelope": {
+                "mailFrom": {"email": "me@example.com", "parameters": {"HOLDUNTIL": "2026-03-02T08:00:00Z"}},
+                "rcptTo": [{"email": "you@example.com", "parameters": null}]
+            },
+            "sendAt": "2026-03-02T08:00:00Z",
+            "undoStatus": "pending"
+        }"#;
+        let sub: EmailSubmission = serde_json::from_str(json).unwrap();
+        assert_eq!(sub.undo_status, "pending");
+        assert_eq!(sub.envelope.unwrap().rcpt_to[0].email, "you@example.com");
+    }
+
     #[test]
     fn test_mailbox_deserialize() {
         let json = r#"{
@@ -500,4 +1307,51 @@ mod tests {
         assert_eq!(masked.description, Some("Test site".to_string()));
         assert_eq!(masked.created_by, Some("fastmail-cli".to_string()));
     }
+
+    #[test]
+    fn test_changes_merge() {
+        let mut changes: Changes = serde_json::from_value(serde_json::json!({
+            "accountId": "u1",
+            "oldState": "s1",
+            "newState": "s2",
+            "hasMoreChanges": true,
+            "created": ["a", "b"],
+            "updated": ["x"],
+            "destroyed": ["y"]
+        }))
+        .unwrap();
+        assert!(changes.has_more_changes);
+
+        changes.merge(Changes {
+            old_state: "s2".into(),
+            new_state: "s3".into(),
+            has_more_changes: false,
+            created: vec!["c".into()],
+            updated: vec!["a".into(), "z".into()],
+            destroyed: vec!["b".into(), "x".into()],
+        });
+        assert_eq!(changes.old_state, "s1");
+        assert_eq!(changes.new_state, "s3");
+        assert!(!changes.has_more_changes);
+        assert_eq!(changes.created, ["a", "c"]);
+        assert_eq!(changes.updated, ["z"]);
+        assert_eq!(changes.destroyed, ["y", "x"]);
+
+        let json = serde_json::to_value(&changes).unwrap();
+        assert_eq!(json["newState"], "s3");
+        assert!(json.get("hasMoreChanges").is_none());
+    }
+
+    #[test]
+    fn test_empty_plan_keeps_emails_in_other_mailboxes() {
+        let emails: Vec<Email> = serde_json::from_value(serde_json::json!([
+            {"id": "only-trash", "mailboxIds": {"trash": true}},
+            {"id": "also-archived", "mailboxIds": {"trash": true, "archive": true}},
+            {"id": "stale-flag", "mailboxIds": {"trash": true, "archive": false}}
+        ]))
+        .unwrap();
+        let plan = EmptyPlan::new(&emails, "trash");
+        assert_eq!(plan.destroy, ["only-trash", "stale-flag"]);
+        assert_eq!(plan.remove, ["also-archived"]);
+    }
 }
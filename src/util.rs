@@ -1,4 +1,5 @@
 use crate::models::EmailAddress;
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone, Utc};
 use std::path::Path;
 
 pub fn parse_addresses(input: &str) -> Vec<EmailAddress> {
@@ -62,6 +63,56 @@ pub async fn extract_text(bytes: &[u8], filename: &str) -> anyhow::Result<Option
     }
 }
 
+/// Render Markdown to an HTML fragment for the htmlBody of an outgoing email
+pub fn markdown_to_html(markdown: &str) -> String {
+    use pulldown_cmark::{Options, Parser, html};
+
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    );
+    let mut output = String::new();
+    html::push_html(&mut output, parser);
+    output
+}
+
+/// Generate a plain-text fallback for an HTML body
+pub async fn html_to_text(html: &str) -> String {
+    match extract_text(html.as_bytes(), "message.html").await {
+        Ok(Some(text)) => text,
+        _ => strip_tags(html),
+    }
+}
+
+/// Crude tag stripper used when kreuzberg can't make sense of the HTML
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+/// Escape text for inclusion in an HTML body
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 /// Synchronous version for non-async contexts
 /// NOTE: Returns None for images - use existing image pipeline instead
 pub fn extract_text_sync(bytes: &[u8], filename: &str) -> anyhow::Result<Option<String>> {
@@ -176,6 +227,13 @@ fn mime_from_filename(filename: &str) -> String {
     .to_string()
 }
 
+/// Infer MIME type for an outgoing attachment from its filename
+pub fn infer_mime(filename: &str) -> String {
+    infer_image_mime(filename)
+        .map(String::from)
+        .unwrap_or_else(|| mime_from_filename(filename))
+}
+
 // ============ Image Processing ============
 
 /// Parse a human-readable size string like "500K", "1M", "1.5MB" into bytes
@@ -281,6 +339,129 @@ pub fn resize_image(
     Ok((output, "image/jpeg".to_string()))
 }
 
+// ============ Queries ============
+
+/// Parse a sort spec like "size", "from:asc" or "receivedAt:desc" into a JMAP Email
+/// sort property and direction (descending unless ":asc" is given)
+pub fn parse_sort(s: &str) -> Option<(&'static str, bool)> {
+    let (property, direction) = match s.split_once(':') {
+        Some((p, d)) => (p, d),
+        None => (s, "desc"),
+    };
+    let property = match property.trim().to_lowercase().as_str() {
+        "receivedat" | "received" | "date" => "receivedAt",
+        "sentat" | "sent" => "sentAt",
+        "size" => "size",
+        "from" => "from",
+        "to" => "to",
+        "subject" => "subject",
+        _ => return None,
+    };
+    let ascending = match direction.trim().to_lowercase().as_str() {
+        "asc" => true,
+        "desc" => false,
+        _ => return None,
+    };
+    Some((property, ascending))
+}
+
+// ============ Scheduling ============
+
+/// Parse a duration like "90s", "15m", "2h", "1d" or "1h30m"; a bare number is seconds
+pub fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim().to_lowercase();
+    if s.is_empty() {
+        return None;
+    }
+    if let Ok(secs) = s.parse::<i64>() {
+        return Some(Duration::seconds(secs));
+    }
+
+    let mut total = Duration::zero();
+    let mut num = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            num.push(c);
+            continue;
+        }
+        let n: i64 = num.parse().ok()?;
+        num.clear();
+        total += match c {
+            's' => Duration::seconds(n),
+            'm' => Duration::minutes(n),
+            'h' => Duration::hours(n),
+            'd' => Duration::days(n),
+            'w' => Duration::weeks(n),
+            _ => return None,
+        };
+    }
+    // Trailing digits without a unit ("1h30")
+    num.is_empty().then_some(total)
+}
+
+/// Parse an RFC 3339 timestamp, or "YYYY-MM-DD HH:MM[:SS]" in local time
+pub fn parse_datetime(s: &str) -> Option<DateTime<Utc>> {
+    let s = s.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ]
+    .iter()
+    .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+    .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+    .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Characters `safe_file_name` keeps from the name, not counting the extension
+const MAX_FILE_STEM: usize = 100;
+
+/// Turn a subject into a file name that works everywhere: path separators, characters
+/// Windows rejects and control characters become `_`, whitespace runs collapse, and
+/// long names are cut. Falls back to `fallback` when nothing usable is left.
+pub fn safe_file_name(name: &str, extension: &str, fallback: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || r#"/\:*?"<>|"#.contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let stem: String = cleaned
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_FILE_STEM)
+        .collect();
+    // Leading dots would hide the file; trailing ones are dropped on Windows
+    let stem = stem.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    let stem = if stem.is_empty() { fallback } else { stem };
+    format!("{}.{}", stem, extension)
+}
+
+/// RFC 8620 autodiscovery URL for an email address or bare domain
+/// (`me@example.com` -> `https://example.com/.well-known/jmap`)
+pub fn well_known_jmap_url(email_or_domain: &str) -> Option<String> {
+    let domain = email_or_domain
+        .trim()
+        .rsplit('@')
+        .next()?
+        .trim_end_matches('.')
+        .to_lowercase();
+    if domain.is_empty() || domain.contains(['/', ':', ' ']) {
+        return None;
+    }
+    Some(format!("https://{}/.well-known/jmap", domain))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -333,6 +514,30 @@ mod tests {
         assert_eq!(result[1].email, "other@example.com");
     }
 
+    #[test]
+    fn test_infer_mime() {
+        assert_eq!(infer_mime("report.pdf"), "application/pdf");
+        assert_eq!(infer_mime("photo.JPG"), "image/jpeg");
+        assert_eq!(infer_mime("data.unknown"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_markdown_to_html() {
+        let html = markdown_to_html("# Status\n\n**Done**: shipped");
+        assert!(html.contains("<h1>Status</h1>"));
+        assert!(html.contains("<strong>Done</strong>"));
+    }
+
+    #[test]
+    fn test_strip_tags() {
+        assert_eq!(strip_tags("<p>Hello &amp; <b>bye</b></p>"), "Hello & bye");
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(escape_html("<a & b>"), "&lt;a &amp; b&gt;");
+    }
+
     #[test]
     fn test_parse_angle_brackets_no_name() {
         let result = parse_addresses("<bare@example.com>");
@@ -340,4 +545,70 @@ mod tests {
         assert_eq!(result[0].email, "bare@example.com");
         assert!(result[0].name.is_none());
     }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Some(Duration::seconds(90)));
+        assert_eq!(parse_duration("15m"), Some(Duration::minutes(15)));
+        assert_eq!(parse_duration("2H"), Some(Duration::hours(2)));
+        assert_eq!(parse_duration("1d"), Some(Duration::days(1)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("1h30"), None);
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn test_parse_datetime() {
+        let dt = parse_datetime("2026-03-02T09:00:00+01:00").unwrap();
+        assert_eq!(dt.to_rfc3339(), "2026-03-02T08:00:00+00:00");
+        assert!(parse_datetime("2026-03-02 09:00").is_some());
+        assert!(parse_datetime("2026-03-02T09:00:30").is_some());
+        assert!(parse_datetime("monday").is_none());
+    }
+
+    #[test]
+    fn test_parse_sort() {
+        assert_eq!(parse_sort("size"), Some(("size", false)));
+        assert_eq!(parse_sort("from:asc"), Some(("from", true)));
+        assert_eq!(parse_sort("receivedAt:desc"), Some(("receivedAt", false)));
+        assert_eq!(parse_sort("SENTAT:ASC"), Some(("sentAt", true)));
+        assert_eq!(parse_sort("colour"), None);
+        assert_eq!(parse_sort("size:up"), None);
+    }
+
+    #[test]
+    fn test_safe_file_name() {
+        assert_eq!(
+            safe_file_name("Re: Q3 report / final?", "eml", "message"),
+            "Re_ Q3 report _ final_.eml"
+        );
+        assert_eq!(
+            safe_file_name("../../etc/passwd", "eml", "message"),
+            "_.._etc_passwd.eml"
+        );
+        assert_eq!(
+            safe_file_name("line\r\nbreak\t tab", "eml", "message"),
+            "line__break_ tab.eml"
+        );
+        assert_eq!(safe_file_name("  ...  ", "eml", "message"), "message.eml");
+        assert_eq!(
+            safe_file_name(&"x".repeat(300), "eml", "message").len(),
+            104
+        );
+    }
+
+    #[test]
+    fn test_well_known_jmap_url() {
+        assert_eq!(
+            well_known_jmap_url("Me@Example.COM").as_deref(),
+            Some("https://example.com/.well-known/jmap")
+        );
+        assert_eq!(
+            well_known_jmap_url("mail.example.org").as_deref(),
+            Some("https://mail.example.org/.well-known/jmap")
+        );
+        assert!(well_known_jmap_url("me@").is_none());
+        assert!(well_known_jmap_url("https://example.com/").is_none());
+    }
 }